@@ -1,7 +1,7 @@
 
 marine build --release
 
-mkdir -p artifacts
+mkdir -p artifacts data
 rm artifacts/*
 cp curl_adapter/target/wasm32-wasi/release/curl_adapter.wasm artifacts/                                   
 cp target/wasm32-wasi/release/academy_backend.wasm artifacts/
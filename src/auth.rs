@@ -1,4 +1,13 @@
 use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::db::{self, Submission};
+use crate::error::{Error, ErrorCode, Result};
+use crate::judging;
+use crate::near;
+
+// How long an issued challenge can be answered.
+const CHALLENGE_TTL: &str = "-5 minutes";
 
 #[marine]
 pub fn get_init_peer_id() -> String {
@@ -6,6 +15,10 @@ pub fn get_init_peer_id() -> String {
     meta.init_peer_id
 }
 
+pub fn get_not_owner_error() -> Error {
+    Error::new(ErrorCode::Unauthorized, "You are not the owner!")
+}
+
 pub fn is_owner() -> bool {
     let meta = marine_rs_sdk::get_call_parameters();
     let caller = meta.init_peer_id;
@@ -18,3 +31,458 @@ pub fn is_owner() -> bool {
 pub fn am_i_owner() -> bool {
     is_owner()
 }
+
+// ROLES
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    SuperAdmin,
+    Admin,
+    Organizer,
+    Judge,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::SuperAdmin => "super_admin",
+            Role::Admin => "admin",
+            Role::Organizer => "organizer",
+            Role::Judge => "judge",
+        }
+    }
+
+    pub fn parse(role: &str) -> Result<Role> {
+        match role {
+            "super_admin" => Ok(Role::SuperAdmin),
+            "admin" => Ok(Role::Admin),
+            "organizer" => Ok(Role::Organizer),
+            "judge" => Ok(Role::Judge),
+            _ => Err(Error::new(
+                ErrorCode::Validation,
+                format!("Unknown role: {}", role),
+            )),
+        }
+    }
+}
+
+pub fn get_unauthorized_error() -> Error {
+    Error::new(ErrorCode::Unauthorized, "You are not authorized!")
+}
+
+/// Records the service creator as super admin. The creator is treated as
+/// super admin by `has_role` regardless, the row only makes it visible in
+/// `get_roles`.
+pub fn bootstrap_owner(conn: &Connection) -> Result<()> {
+    let meta = marine_rs_sdk::get_call_parameters();
+    let owner = meta.service_creator_peer_id;
+
+    db::execute(
+        conn,
+        "insert or ignore into roles (peer_id, role, granted_by) values (?, ?, ?);",
+        &[
+            Value::String(owner.clone()),
+            Value::String(Role::SuperAdmin.as_str().to_string()),
+            Value::String(owner),
+        ],
+    )
+}
+
+pub fn get_roles(conn: &Connection, peer_id: String) -> Result<Vec<Role>> {
+    let roles = db::query_all(
+        conn,
+        "select role from roles where peer_id = ?;",
+        &[Value::String(peer_id)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )?;
+
+    Ok(roles.iter().filter_map(|r| Role::parse(r).ok()).collect())
+}
+
+/// Whether `peer_id` holds `role`. Super admins and admins hold every role
+/// below them, and the service creator is always a super admin.
+pub fn has_role(conn: &Connection, peer_id: String, role: Role) -> Result<bool> {
+    let meta = marine_rs_sdk::get_call_parameters();
+    if peer_id == meta.service_creator_peer_id {
+        return Ok(true);
+    }
+
+    let roles = get_roles(conn, peer_id)?;
+    let allowed = roles.iter().any(|r| match r {
+        Role::SuperAdmin => true,
+        Role::Admin => role != Role::SuperAdmin,
+        _ => *r == role,
+    });
+
+    Ok(allowed)
+}
+
+/// Fails with `get_unauthorized_error` unless the calling peer holds `role`.
+pub fn require_role(conn: &Connection, role: Role) -> Result<()> {
+    if has_role(conn, get_init_peer_id(), role)? {
+        Ok(())
+    } else {
+        Err(get_unauthorized_error())
+    }
+}
+
+/// Granting admin rights needs a super admin, every other role an admin.
+fn required_to_manage(role: Role) -> Role {
+    match role {
+        Role::SuperAdmin | Role::Admin => Role::SuperAdmin,
+        _ => Role::Admin,
+    }
+}
+
+pub fn grant_role(conn: &Connection, peer_id: String, role: Role) -> Result<()> {
+    require_role(conn, required_to_manage(role))?;
+
+    db::execute(
+        conn,
+        "insert or ignore into roles (peer_id, role, granted_by) values (?, ?, ?);",
+        &[
+            Value::String(peer_id),
+            Value::String(role.as_str().to_string()),
+            Value::String(get_init_peer_id()),
+        ],
+    )
+}
+
+pub fn revoke_role(conn: &Connection, peer_id: String, role: Role) -> Result<()> {
+    require_role(conn, required_to_manage(role))?;
+
+    db::execute(
+        conn,
+        "delete from roles where peer_id = ? AND role = ?;",
+        &[
+            Value::String(peer_id),
+            Value::String(role.as_str().to_string()),
+        ],
+    )
+}
+
+// NEAR OWNERSHIP
+#[marine]
+#[derive(Default)]
+pub struct Challenge {
+    pub near_address: String,
+    pub message: String,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Challenge {
+    pub fn from_res(res: Result<Challenge>) -> Challenge {
+        match res {
+            Ok(v) => v,
+            Err(e) => Challenge {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// The exact text the NEAR key has to sign. It names the calling peer so a
+/// signature cannot be replayed by another peer.
+pub fn challenge_message(near_address: &str, peer_id: &str, nonce: &str) -> String {
+    format!(
+        "academy_backend: prove ownership of {} for peer {} with nonce {}",
+        near_address, peer_id, nonce
+    )
+}
+
+pub fn issue_challenge(conn: &Connection, near_address: String) -> Result<Challenge> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|_| Error::new(ErrorCode::Internal, "Unable to generate nonce"))?;
+    let nonce = hex::encode(bytes);
+    let peer_id = get_init_peer_id();
+
+    db::execute(
+        conn,
+        "insert or replace into near_challenges (near_address, peer_id, nonce) values (?, ?, ?);",
+        &[
+            Value::String(near_address.clone()),
+            Value::String(peer_id.clone()),
+            Value::String(nonce.clone()),
+        ],
+    )?;
+
+    Ok(Challenge {
+        message: challenge_message(&near_address, &peer_id, &nonce),
+        near_address,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
+
+/// Removes the calling peer's pending challenge for `near_address` and
+/// returns its nonce. A challenge can only be answered once, so this has to
+/// be committed even when the answer turns out to be wrong.
+pub fn take_challenge(conn: &Connection, near_address: String) -> Result<String> {
+    let peer_id = get_init_peer_id();
+    let challenge_error = || {
+        Error::new(
+            ErrorCode::NotFound,
+            "No pending challenge, request a new one",
+        )
+    };
+
+    let nonce = db::query_one(
+        conn,
+        "
+        select nonce from near_challenges
+        where near_address = ? AND peer_id = ? AND created_at > datetime('now', ?);
+        ",
+        &[
+            Value::String(near_address.clone()),
+            Value::String(peer_id.clone()),
+            Value::String(CHALLENGE_TTL.to_string()),
+        ],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+    .map_err(|_| challenge_error())?;
+
+    db::execute(
+        conn,
+        "delete from near_challenges where near_address = ? AND peer_id = ?;",
+        &[Value::String(near_address), Value::String(peer_id)],
+    )?;
+
+    Ok(nonce)
+}
+
+/// Records `public_key` as an access key of the named account
+/// `near_address`. The module cannot query NEAR for an account's keys, so an
+/// organizer attests them after checking the chain.
+pub fn attest_access_key(
+    conn: &Connection,
+    near_address: String,
+    public_key: String,
+) -> Result<()> {
+    if near::is_implicit_account(&near_address) {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Implicit accounts are verified by their key, no attestation needed",
+        ));
+    }
+    near::parse_public_key(&public_key)?;
+
+    db::execute(
+        conn,
+        "
+        insert into near_access_keys (near_address, public_key, attested_by)
+        values (?, ?, ?)
+        on conflict (near_address, public_key) do nothing;
+        ",
+        &[
+            Value::String(near_address),
+            Value::String(public_key),
+            Value::String(get_init_peer_id()),
+        ],
+    )
+}
+
+/// Withdraws an attested key along with any binding proven with it.
+pub fn revoke_access_key(
+    conn: &Connection,
+    near_address: String,
+    public_key: String,
+) -> Result<()> {
+    let params = [Value::String(near_address), Value::String(public_key)];
+
+    db::execute(
+        conn,
+        "delete from near_access_keys where near_address = ? AND public_key = ?;",
+        &params,
+    )?;
+    db::execute(
+        conn,
+        "delete from peer_bindings where near_address = ? AND public_key = ?;",
+        &params,
+    )
+}
+
+pub fn get_access_keys(conn: &Connection, near_address: String) -> Result<Vec<String>> {
+    db::query_all(
+        conn,
+        "select public_key from near_access_keys where near_address = ? order by created_at;",
+        &[Value::String(near_address)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+}
+
+fn is_access_key(conn: &Connection, near_address: &str, public_key: &str) -> Result<bool> {
+    if near::is_implicit_account(near_address) {
+        return Ok(near::implicit_account_matches(near_address, public_key));
+    }
+
+    db::query_one(
+        conn,
+        "select count(*) from near_access_keys where near_address = ? AND public_key = ?;",
+        &[
+            Value::String(near_address.to_string()),
+            Value::String(public_key.to_string()),
+        ],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )
+}
+
+/// Checks `signature` over the challenge with `nonce` and binds
+/// `near_address` to the calling peer. Implicit accounts must match
+/// `public_key`; named accounts must use a key an organizer attested with
+/// `attest_access_key`.
+pub fn verify_near_address(
+    conn: &Connection,
+    near_address: String,
+    public_key: String,
+    signature: String,
+    nonce: &str,
+) -> Result<()> {
+    let peer_id = get_init_peer_id();
+
+    if !is_access_key(conn, &near_address, &public_key)? {
+        return Err(Error::new(
+            ErrorCode::Unauthorized,
+            "Public key is not a known access key of this account",
+        ));
+    }
+
+    let message = challenge_message(&near_address, &peer_id, nonce);
+    near::verify_signature(&public_key, &message, &signature)?;
+
+    db::execute(
+        conn,
+        "delete from peer_bindings where near_address = ? OR peer_id = ?;",
+        &[
+            Value::String(near_address.clone()),
+            Value::String(peer_id.clone()),
+        ],
+    )?;
+    db::execute(
+        conn,
+        "insert into peer_bindings (peer_id, near_address, public_key) values (?, ?, ?);",
+        &[
+            Value::String(peer_id),
+            Value::String(near_address),
+            Value::String(public_key),
+        ],
+    )
+}
+
+/// Fails unless the calling peer has proven ownership of `near_address`.
+pub fn require_near_address(conn: &Connection, near_address: String) -> Result<()> {
+    let bound = db::query_one(
+        conn,
+        "select count(*) from peer_bindings where peer_id = ? AND near_address = ?;",
+        &[
+            Value::String(get_init_peer_id()),
+            Value::String(near_address),
+        ],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )?;
+
+    if bound {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorCode::Unauthorized,
+            "Prove ownership of this NEAR address first",
+        ))
+    }
+}
+
+// PEER BINDINGS
+pub fn get_unbound_error() -> Error {
+    Error::new(
+        ErrorCode::Unauthorized,
+        "No NEAR address is bound to this peer",
+    )
+}
+
+/// NEAR address the calling peer proved ownership of.
+pub fn get_caller_address(conn: &Connection) -> Result<String> {
+    db::query_one(
+        conn,
+        "select near_address from peer_bindings where peer_id = ?;",
+        &[Value::String(get_init_peer_id())],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+    .map_err(|_| get_unbound_error())
+}
+
+/// Fails unless the calling peer is bound to the user who owns `submission`.
+pub fn require_submission_owner(conn: &Connection, submission: &Submission) -> Result<()> {
+    let caller = get_caller_address(conn)?;
+
+    if caller == submission.submit_by {
+        Ok(())
+    } else {
+        Err(get_unauthorized_error())
+    }
+}
+
+/// Fails unless the calling peer is the owner or a team member of
+/// `submission`.
+pub fn require_submission_member(conn: &Connection, submission: &Submission) -> Result<()> {
+    let caller = get_caller_address(conn)?;
+
+    if caller == submission.submit_by || db::is_team_member(conn, submission.uuid, caller)? {
+        Ok(())
+    } else {
+        Err(get_unauthorized_error())
+    }
+}
+
+/// Whether a peer bound to `near_address` holds `role`. Addresses nobody has
+/// proven ownership of hold no role.
+fn address_has_role(conn: &Connection, near_address: String, role: Role) -> Result<bool> {
+    let peers = db::query_all(
+        conn,
+        "select peer_id from peer_bindings where near_address = ?;",
+        &[Value::String(near_address)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )?;
+
+    for peer_id in peers {
+        if has_role(conn, peer_id, role)? {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Fails unless `near_address` belongs to a judge, which organizers check
+/// before attaching them to an event.
+pub fn require_judge(conn: &Connection, near_address: String) -> Result<()> {
+    if address_has_role(conn, near_address, Role::Judge)? {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorCode::Validation,
+            "Grant the judge role to this user first",
+        ))
+    }
+}
+
+/// Fails unless the calling peer holds the judge role and is bound to a
+/// judge of `event_id`, and returns that judge's address.
+pub fn require_event_judge(conn: &Connection, event_id: i64) -> Result<String> {
+    require_role(conn, Role::Judge)?;
+    let caller = get_caller_address(conn)?;
+
+    if judging::is_event_judge(conn, event_id, caller.clone())? {
+        Ok(caller)
+    } else {
+        Err(get_unauthorized_error())
+    }
+}
+
+pub fn is_organizer(conn: &Connection) -> Result<bool> {
+    has_role(conn, get_init_peer_id(), Role::Organizer)
+}
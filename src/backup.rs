@@ -0,0 +1,253 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+use serde_json::{json, Map};
+
+use crate::db;
+use crate::error::{Error, ErrorCode, Result};
+use crate::migrations;
+use crate::schedule;
+
+// Bumped when the layout of the dump itself changes. The database schema is
+// versioned separately through `schema_version`.
+pub const BACKUP_FORMAT: i64 = 1;
+
+#[marine]
+#[derive(Default)]
+pub struct Backup {
+    pub dump: String,
+    pub schema_version: i64,
+    pub tables: i64,
+    pub rows: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Backup {
+    pub fn from_res(res: Result<Backup>) -> Backup {
+        match res {
+            Ok(v) => v,
+            Err(e) => Backup {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn get_dump_error(msg: &str) -> Error {
+    Error::new(ErrorCode::Validation, format!("Invalid backup: {}", msg))
+}
+
+/// Every data table in creation order, which is also an order the insert
+/// triggers accept on restore. `schema_version` is left out, the dump
+/// records the version instead, and so are the reset archives.
+pub fn get_tables(conn: &Connection) -> Result<Vec<String>> {
+    let tables = db::query_all(
+        conn,
+        "
+        select name from sqlite_master
+        where type = 'table' AND name not like 'sqlite_%' AND name != 'schema_version'
+        order by rowid;
+        ",
+        &[],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )?;
+
+    Ok(tables
+        .into_iter()
+        .filter(|t| !db::RESET_KEPT_TABLES.contains(&t.as_str()))
+        .collect())
+}
+
+fn get_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    db::query_all(
+        conn,
+        &format!("pragma table_info(\"{}\");", table),
+        &[],
+        |row| Ok(row[1].as_string().unwrap_or_default().to_string()),
+    )
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Integer(v) => json!(v),
+        Value::Float(v) => json!(v),
+        Value::String(v) => json!(v),
+        Value::Binary(v) => json!({ "hex": hex::encode(v) }),
+        Value::Null => serde_json::Value::Null,
+    }
+}
+
+fn from_json(value: &serde_json::Value) -> Result<Value> {
+    match value {
+        serde_json::Value::Null => Ok(Value::Null),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(v) => Ok(Value::Integer(v)),
+            None => Ok(Value::Float(n.as_f64().unwrap_or_default())),
+        },
+        serde_json::Value::String(s) => Ok(Value::String(s.clone())),
+        serde_json::Value::Object(o) => match o.get("hex").and_then(|h| h.as_str()) {
+            Some(h) => hex::decode(h)
+                .map(Value::Binary)
+                .map_err(|_| get_dump_error("bad binary value")),
+            None => Err(get_dump_error("unexpected object value")),
+        },
+        _ => Err(get_dump_error("unexpected value")),
+    }
+}
+
+/// Serializes every table into a JSON document:
+/// `{ format, schema_version, created_at, tables: [{ name, columns, rows }] }`.
+pub fn backup(conn: &Connection) -> Result<Backup> {
+    let schema_version = migrations::current_version(conn)?;
+
+    let mut tables = Vec::new();
+    let mut row_count = 0;
+    for table in get_tables(conn)? {
+        let columns = get_columns(conn, &table)?;
+        let rows = db::query_all(conn, &format!("select * from \"{}\";", table), &[], |row| {
+            Ok(serde_json::Value::Array(row.iter().map(to_json).collect()))
+        })?;
+
+        row_count += rows.len() as i64;
+        tables.push(json!({ "name": table, "columns": columns, "rows": rows }));
+    }
+
+    let table_count = tables.len() as i64;
+    let dump = json!({
+        "format": BACKUP_FORMAT,
+        "schema_version": schema_version,
+        "created_at": schedule::now()?,
+        "tables": tables,
+    });
+
+    Ok(Backup {
+        dump: dump.to_string(),
+        schema_version,
+        tables: table_count,
+        rows: row_count,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
+
+fn restore_tables(
+    conn: &Connection,
+    current: &[String],
+    tables: &[serde_json::Value],
+) -> Result<()> {
+    for table in current.iter().rev() {
+        db::execute(conn, &format!("delete from \"{}\";", table), &[])?;
+    }
+
+    for table in tables {
+        let table = table
+            .as_object()
+            .ok_or_else(|| get_dump_error("table is not an object"))?;
+        insert_rows(conn, current, table)?;
+    }
+
+    Ok(())
+}
+
+fn insert_rows(
+    conn: &Connection,
+    current: &[String],
+    table: &Map<String, serde_json::Value>,
+) -> Result<()> {
+    let name = table
+        .get("name")
+        .and_then(|n| n.as_str())
+        .ok_or_else(|| get_dump_error("table without a name"))?;
+
+    // names are checked against the live schema before they reach the SQL
+    if !current.iter().any(|t| t == name) {
+        return Err(get_dump_error(&format!("unknown table {}", name)));
+    }
+
+    let known = get_columns(conn, name)?;
+    let columns: Vec<String> = table
+        .get("columns")
+        .and_then(|c| c.as_array())
+        .ok_or_else(|| get_dump_error(&format!("no columns for {}", name)))?
+        .iter()
+        .map(|c| c.as_str().unwrap_or_default().to_string())
+        .collect();
+    if let Some(column) = columns.iter().find(|c| !known.contains(c)) {
+        return Err(get_dump_error(&format!(
+            "unknown column {}.{}",
+            name, column
+        )));
+    }
+
+    let sql = format!(
+        "insert into \"{}\" ({}) values ({});",
+        name,
+        columns
+            .iter()
+            .map(|c| format!("\"{}\"", c))
+            .collect::<Vec<String>>()
+            .join(", "),
+        vec!["?"; columns.len()].join(", ")
+    );
+
+    let rows = table
+        .get("rows")
+        .and_then(|r| r.as_array())
+        .ok_or_else(|| get_dump_error(&format!("no rows for {}", name)))?;
+    for row in rows {
+        let values = row
+            .as_array()
+            .filter(|r| r.len() == columns.len())
+            .ok_or_else(|| get_dump_error(&format!("malformed row in {}", name)))?
+            .iter()
+            .map(from_json)
+            .collect::<Result<Vec<Value>>>()?;
+
+        db::execute(conn, &sql, &values)?;
+    }
+
+    Ok(())
+}
+
+/// Replaces the contents of every table with those of `dump`. The dump must
+/// come from the same schema version as the database; the whole restore
+/// runs in one transaction, so any failure leaves the data untouched.
+pub fn restore(conn: &Connection, dump: &str) -> Result<()> {
+    let dump: serde_json::Value =
+        serde_json::from_str(dump).map_err(|e| get_dump_error(&e.to_string()))?;
+
+    let format = dump["format"].as_i64().unwrap_or_default();
+    if format != BACKUP_FORMAT {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            format!(
+                "Backup format {} is not supported, expected {}",
+                format, BACKUP_FORMAT
+            ),
+        ));
+    }
+
+    let version = migrations::current_version(conn)?;
+    let dump_version = dump["schema_version"].as_i64().unwrap_or_default();
+    if dump_version != version {
+        return Err(Error::new(
+            ErrorCode::InvalidState,
+            format!(
+                "Backup is from schema version {} but the database is at {}",
+                dump_version, version
+            ),
+        ));
+    }
+
+    let tables = dump["tables"]
+        .as_array()
+        .ok_or_else(|| get_dump_error("no tables"))?;
+    let current = get_tables(conn)?;
+
+    db::transaction(conn, || restore_tables(conn, &current, tables))
+}
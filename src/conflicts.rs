@@ -0,0 +1,359 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::db::{self, get_none_error, Submission};
+use crate::error::{Error, ErrorCode, Result};
+use crate::judging;
+use crate::status::SubmissionStatus;
+
+pub const CONFLICT_SUBMITTER: &str = "SUBMITTER";
+pub const CONFLICT_TEAM_MEMBER: &str = "TEAM_MEMBER";
+pub const CONFLICT_ORGANIZATION: &str = "ORGANIZATION";
+
+const MAX_ORGANIZATIONS: usize = 10;
+
+// ORGANIZATIONS
+/// Lowercased with whitespace collapsed, so "Acme  Labs" and "acme labs"
+/// count as the same organization.
+pub fn normalize_organization(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Replaces the organizations `near_address` declares affiliation with.
+pub fn set_organizations(
+    conn: &Connection,
+    near_address: String,
+    organizations: Vec<String>,
+) -> Result<()> {
+    let mut names: Vec<String> = organizations
+        .iter()
+        .map(|o| normalize_organization(o))
+        .filter(|o| !o.is_empty())
+        .collect();
+    names.sort();
+    names.dedup();
+
+    if names.len() > MAX_ORGANIZATIONS {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            format!(
+                "At most {} organizations can be declared",
+                MAX_ORGANIZATIONS
+            ),
+        ));
+    }
+
+    db::execute(
+        conn,
+        "delete from user_organizations where near_address = ?;",
+        &[Value::String(near_address.clone())],
+    )?;
+
+    for name in names {
+        db::execute(
+            conn,
+            "insert into user_organizations (near_address, organization) values (?, ?);",
+            &[Value::String(near_address.clone()), Value::String(name)],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn get_organizations(conn: &Connection, near_address: String) -> Result<Vec<String>> {
+    db::query_all(
+        conn,
+        "select organization from user_organizations where near_address = ? order by organization;",
+        &[Value::String(near_address)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+}
+
+// DETECTION
+#[marine]
+#[derive(Default, Clone)]
+pub struct JudgeConflict {
+    pub submission_id: i64,
+    pub judge: String,
+    pub reason: String,
+    pub detail: String,
+    pub detected_at: String,
+    pub suggested_judges: Vec<String>,
+}
+
+impl JudgeConflict {
+    pub fn from_row(row: &[Value]) -> Result<JudgeConflict> {
+        Ok(JudgeConflict {
+            submission_id: row[1].as_integer().ok_or(get_none_error())?,
+            judge: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            reason: row[3].as_string().unwrap_or_default().to_string(),
+            detail: row[4].as_string().unwrap_or_default().to_string(),
+            detected_at: row[5].as_string().unwrap_or_default().to_string(),
+            suggested_judges: Vec::new(),
+        })
+    }
+}
+
+#[marine]
+#[derive(Default)]
+pub struct ConflictReport {
+    pub event_id: i64,
+    pub conflicts: Vec<JudgeConflict>,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl ConflictReport {
+    pub fn from_res(res: Result<Vec<JudgeConflict>>, event_id: i64) -> ConflictReport {
+        match res {
+            Ok(conflicts) => ConflictReport {
+                event_id,
+                conflicts,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => ConflictReport {
+                event_id,
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Why `judge` cannot review `submission`, as a reason code and a detail,
+/// or `None` when they are independent. A judge conflicts with their own
+/// project, a project they are on the team of, and a project whose owner or
+/// team member declares an organization the judge also declares.
+pub fn find_conflict(
+    conn: &Connection,
+    judge: &str,
+    submission: &Submission,
+) -> Result<Option<(&'static str, String)>> {
+    if judge == submission.submit_by {
+        return Ok(Some((
+            CONFLICT_SUBMITTER,
+            "Judge submitted this project".to_string(),
+        )));
+    }
+
+    if db::is_team_member(conn, submission.uuid, judge.to_string())? {
+        return Ok(Some((
+            CONFLICT_TEAM_MEMBER,
+            "Judge is on this project's team".to_string(),
+        )));
+    }
+
+    let shared = db::query_all(
+        conn,
+        "
+        select j.organization, m.near_address from user_organizations j
+        join user_organizations m on m.organization = j.organization
+        where j.near_address = ?
+        AND (
+          m.near_address = (select submit_by from submissions where uuid = ?)
+          OR m.near_address in (select near_address from submission_team where submission_id = ?)
+        )
+        order by j.organization, m.near_address
+        limit 1;
+        ",
+        &[
+            Value::String(judge.to_string()),
+            Value::Integer(submission.uuid),
+            Value::Integer(submission.uuid),
+        ],
+        |row| {
+            Ok((
+                row[0].as_string().unwrap_or_default().to_string(),
+                row[1].as_string().unwrap_or_default().to_string(),
+            ))
+        },
+    )?;
+
+    Ok(shared.into_iter().next().map(|(organization, member)| {
+        (
+            CONFLICT_ORGANIZATION,
+            format!(
+                "Judge shares organization \"{}\" with {}",
+                organization, member
+            ),
+        )
+    }))
+}
+
+fn record_conflict(
+    conn: &Connection,
+    submission: &Submission,
+    judge: &str,
+    reason: &str,
+    detail: &str,
+) -> Result<()> {
+    db::execute(
+        conn,
+        "
+        insert into judge_conflicts (event_id, submission_id, judge, reason, detail)
+        values (?, ?, ?, ?, ?)
+        on conflict (submission_id, judge) do update
+        set reason = excluded.reason, detail = excluded.detail;
+        ",
+        &[
+            Value::Integer(submission.event_id),
+            Value::Integer(submission.uuid),
+            Value::String(judge.to_string()),
+            Value::String(reason.to_string()),
+            Value::String(detail.to_string()),
+        ],
+    )
+}
+
+/// Fails with `CONFLICT` when `judge` is not independent of `submission`,
+/// recording the conflict first.
+pub fn require_independent(conn: &Connection, judge: &str, submission: &Submission) -> Result<()> {
+    match find_conflict(conn, judge, submission)? {
+        Some((reason, detail)) => {
+            record_conflict(conn, submission, judge, reason, &detail)?;
+            Err(Error::new(
+                ErrorCode::Conflict,
+                format!("Conflict of interest: {}", detail),
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Re-checks every judge of the event against every submitted project and
+/// brings the recorded conflicts in line; conflicts that no longer hold,
+/// including those of judges removed from the event, are deleted, new ones
+/// keep their first detection time.
+pub fn detect_conflicts(conn: &Connection, event_id: i64) -> Result<()> {
+    let judges = judging::get_event_judges(conn, event_id)?;
+    let submissions = judging::get_judging_submissions(conn, event_id)?;
+
+    db::execute(
+        conn,
+        "
+        delete from judge_conflicts where event_id = ?
+        AND submission_id not in (select uuid from submissions where event_id = ? AND status = ?);
+        ",
+        &[
+            Value::Integer(event_id),
+            Value::Integer(event_id),
+            Value::Integer(SubmissionStatus::Submitted.code()),
+        ],
+    )?;
+    db::execute(
+        conn,
+        "
+        delete from judge_conflicts where event_id = ?
+        AND judge not in (select near_address from event_judges where event_id = ?);
+        ",
+        &[Value::Integer(event_id), Value::Integer(event_id)],
+    )?;
+
+    for submission in &submissions {
+        for judge in &judges {
+            match find_conflict(conn, &judge.near_address, submission)? {
+                Some((reason, detail)) => {
+                    record_conflict(conn, submission, &judge.near_address, reason, &detail)?
+                }
+                None => db::execute(
+                    conn,
+                    "delete from judge_conflicts where submission_id = ? AND judge = ?;",
+                    &[
+                        Value::Integer(submission.uuid),
+                        Value::String(judge.near_address.clone()),
+                    ],
+                )?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Number of projects a judge is assigned to or has reviewed in the event,
+/// used to prefer the least loaded judge when suggesting a replacement.
+pub fn judge_load(conn: &Connection, event_id: i64, judge: &str) -> Result<i64> {
+    db::query_one(
+        conn,
+        "
+        select count(*) from (
+          select submission_id from review_assignments where event_id = ? AND judge = ?
+          union
+          select r.submission_id from reviews r
+          join submissions s on s.uuid = r.submission_id
+          where s.event_id = ? AND r.judge = ?
+        );
+        ",
+        &[
+            Value::Integer(event_id),
+            Value::String(judge.to_string()),
+            Value::Integer(event_id),
+            Value::String(judge.to_string()),
+        ],
+        |row| Ok(row[0].as_integer().unwrap_or_default()),
+    )
+}
+
+/// Judges already assigned to or who already reviewed `submission_id`.
+fn get_submission_judges(conn: &Connection, submission_id: i64) -> Result<Vec<String>> {
+    db::query_all(
+        conn,
+        "
+        select judge from review_assignments where submission_id = ?
+        union
+        select judge from reviews where submission_id = ?;
+        ",
+        &[Value::Integer(submission_id), Value::Integer(submission_id)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+}
+
+/// Judges of the event with no conflict on `submission_id` and not already
+/// reviewing it, least loaded first.
+pub fn suggest_judges(conn: &Connection, event_id: i64, submission_id: i64) -> Result<Vec<String>> {
+    let submission = db::get_submission(conn, submission_id)?;
+    let reviewing = get_submission_judges(conn, submission_id)?;
+
+    let mut candidates = Vec::new();
+    for judge in judging::get_event_judges(conn, event_id)? {
+        if reviewing.contains(&judge.near_address) {
+            continue;
+        }
+        if find_conflict(conn, &judge.near_address, &submission)?.is_none() {
+            let load = judge_load(conn, event_id, &judge.near_address)?;
+            candidates.push((load, judge.near_address));
+        }
+    }
+    candidates.sort();
+
+    Ok(candidates.into_iter().map(|(_, judge)| judge).collect())
+}
+
+/// Current conflicts of the event, each with the independent judges that
+/// could review the project instead. Runs `detect_conflicts` first, so
+/// reading the report also rewrites the event's rows in `judge_conflicts`.
+pub fn get_judge_conflicts(conn: &Connection, event_id: i64) -> Result<Vec<JudgeConflict>> {
+    db::get_event(conn, event_id)?;
+    detect_conflicts(conn, event_id)?;
+
+    let mut conflicts = db::query_all(
+        conn,
+        "select * from judge_conflicts where event_id = ? order by submission_id, judge;",
+        &[Value::Integer(event_id)],
+        JudgeConflict::from_row,
+    )?;
+
+    for conflict in conflicts.iter_mut() {
+        conflict.suggested_judges = suggest_judges(conn, event_id, conflict.submission_id)?;
+    }
+
+    Ok(conflicts)
+}
@@ -1,118 +1,257 @@
+use std::cell::Cell;
+
 use marine_rs_sdk::marine;
-use marine_sqlite_connector::{Connection, Error, Result, Value};
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::error::{Error, ErrorCode, Result};
+use crate::leaderboard;
+use crate::migrations;
+use crate::page::{self, Filters, Page, PageRequest, SortColumn};
+use crate::schedule;
+use crate::search;
+use crate::status::{self, EventPhase, SubmissionStatus};
 
-const DB_PATH: &str = "/tmp/submission_service_db.sqlite";
+// DATABASE LOCATION
+// The file is opened by the sqlite3 module, so its directory must be mapped
+// into that module. `ACADEMY_DB_PATH` names the file outright and
+// `ACADEMY_DB_DIR` only its directory, both set through this module's wasi
+// `envs`. Without either the database lands in /tmp, which does not survive
+// a node restart.
+pub const DB_PATH_ENV: &str = "ACADEMY_DB_PATH";
+pub const DB_DIR_ENV: &str = "ACADEMY_DB_DIR";
+const DB_FILE_NAME: &str = "submission_service_db.sqlite";
+const DEFAULT_DB_DIR: &str = "/tmp";
 
 pub fn get_none_error() -> Error {
-    Error {
-        code: None,
-        message: Some("Value doesn't exist".to_string()),
+    Error::new(ErrorCode::NotFound, "Value doesn't exist")
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .ok()
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// The configured database file and the setting it came from.
+pub fn db_location() -> (String, &'static str) {
+    if let Some(path) = env_var(DB_PATH_ENV) {
+        return (path, DB_PATH_ENV);
     }
+
+    if let Some(dir) = env_var(DB_DIR_ENV) {
+        let path = format!("{}/{}", dir.trim_end_matches('/'), DB_FILE_NAME);
+        return (path, DB_DIR_ENV);
+    }
+
+    (format!("{}/{}", DEFAULT_DB_DIR, DB_FILE_NAME), "default")
 }
 
-pub fn get_connection() -> Connection {
-    Connection::open(DB_PATH).unwrap()
+pub fn get_connection() -> Result<Connection> {
+    Ok(Connection::open(db_location().0)?)
 }
 
-pub fn create_tables(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "
-      create table if not exists users (
-        near_address TEXT unique not null primary key,
-        email TEXT unique not null,
-        first_name TEXT default null,
-        last_name TEXT default null,
-        is_student INTEGER,
-        country TEXT default null,
-        git_handler TEXT default null,
-        linkedin_handler TEXT default null,
-        twitter_handler TEXT default null
-      ) without rowid;
-      ",
-    )?;
+#[marine]
+#[derive(Default)]
+pub struct StorageInfo {
+    pub db_path: String,
+    pub source: String,
+    pub opened_file: String,
+    pub persistent: bool,
+    pub schema_version: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
 
-    conn.execute(
-        "
-      create table if not exists events (
-        id INTEGER not null primary key AUTOINCREMENT, 
-        type TEXT not null,
-        title TEXT not null,
-        start_date TEXT not null,
-        end_date TEXT default null,
-        logo TEXT not null,
-        status INTEGER not null
-      );
-      ",
-    )?;
+impl StorageInfo {
+    pub fn from_res(res: Result<StorageInfo>) -> StorageInfo {
+        match res {
+            Ok(v) => v,
+            Err(e) => {
+                let (db_path, source) = db_location();
+                StorageInfo {
+                    db_path,
+                    source: source.to_string(),
+                    err_msg: e.to_string(),
+                    err_code: e.code.as_str().to_string(),
+                    success: false,
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
 
-    conn.execute(
-        "
-      create table if not exists submissions (
-        uuid INTEGER not null primary key AUTOINCREMENT, 
-        event_id INTEGER not null,
-        project_name TEXT not null,
-        description TEXT not null,
-        thumbnail TEXT default null,
-        git_url TEXT not null,
-        live_demo_url TEXT default null,
-        video_demo_url TEXT not null,
-        submit_by TEXT not null,
-        status INTEGER not null,
-        created_at DATETIME default CURRENT_TIMESTAMP,
-        FOREIGN KEY (submit_by) REFERENCES users,
-        FOREIGN KEY (event_id) REFERENCES events
-      );
-      ",
-    )?;
+/// Opens the configured database and asks SQLite which file it is actually
+/// using, so a missing mount shows up as an error rather than as data
+/// silently written elsewhere.
+pub fn check_storage() -> Result<StorageInfo> {
+    let (db_path, source) = db_location();
+    let conn = get_connection()?;
 
-    conn.execute(
-        "
-      create table if not exists submission_team (
-        uuid INTEGER not null primary key AUTOINCREMENT, 
-        near_address TEXT not null,
-        created_at DATETIME default CURRENT_TIMESTAMP
-      );
-      ",
-    )?;
+    let opened_file = query_one(&conn, "pragma database_list;", &[], |row| {
+        Ok(row[2].as_string().unwrap_or_default().to_string())
+    })?;
+    let schema_version = migrations::current_version(&conn)?;
+
+    Ok(StorageInfo {
+        persistent: !db_path.starts_with(&format!("{}/", DEFAULT_DB_DIR)),
+        db_path,
+        source: source.to_string(),
+        opened_file,
+        schema_version,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
+
+// QUERY LAYER
+// `params` are bound to the `?` placeholders of `sql` in order. Every user
+// supplied value must reach SQLite through these helpers rather than being
+// formatted into the statement text.
+
+/// Runs a statement that returns no rows (insert, update, delete).
+pub fn execute(conn: &Connection, sql: &str, params: &[Value]) -> Result<()> {
+    let mut cursor = conn.prepare(sql)?.cursor();
+    cursor.bind(params)?;
+    while cursor.next()?.is_some() {}
 
     Ok(())
 }
 
+/// Maps the first row returned by `sql`, failing with `get_none_error` when
+/// there is none.
+pub fn query_one<T>(
+    conn: &Connection,
+    sql: &str,
+    params: &[Value],
+    from_row: fn(&[Value]) -> Result<T>,
+) -> Result<T> {
+    let mut cursor = conn.prepare(sql)?.cursor();
+    cursor.bind(params)?;
+
+    let row = cursor.next()?.ok_or_else(get_none_error)?;
+    from_row(row)
+}
+
+/// Maps every row returned by `sql`.
+pub fn query_all<T>(
+    conn: &Connection,
+    sql: &str,
+    params: &[Value],
+    from_row: fn(&[Value]) -> Result<T>,
+) -> Result<Vec<T>> {
+    let mut cursor = conn.prepare(sql)?.cursor();
+    cursor.bind(params)?;
+
+    let mut items = Vec::new();
+    while let Some(row) = cursor.next()? {
+        items.push(from_row(row)?);
+    }
+
+    Ok(items)
+}
+
+// TRANSACTIONS
+thread_local! {
+    static IN_TRANSACTION: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` inside a `BEGIN IMMEDIATE` transaction, committing when it
+/// returns `Ok` and rolling back on any error. The write lock is taken up
+/// front, so concurrent particles cannot interleave between a check and the
+/// write that depends on it. A call made while a transaction is already open
+/// joins it instead of starting a nested one.
+pub fn transaction<T, F>(conn: &Connection, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    if IN_TRANSACTION.with(|t| t.get()) {
+        return f();
+    }
+
+    conn.execute("BEGIN IMMEDIATE;")?;
+    IN_TRANSACTION.with(|t| t.set(true));
+
+    let res = f().and_then(|v| {
+        conn.execute("COMMIT;")?;
+        Ok(v)
+    });
+
+    IN_TRANSACTION.with(|t| t.set(false));
+
+    if let Err(e) = &res {
+        if let Err(rollback) = conn.execute("ROLLBACK;") {
+            log::error!("rollback after \"{}\" failed: {}", e, rollback);
+        }
+    }
+
+    res
+}
+
+pub fn last_insert_rowid(conn: &Connection) -> Result<i64> {
+    query_one(conn, "select last_insert_rowid();", &[], |row| {
+        row[0].as_integer().ok_or_else(get_none_error)
+    })
+}
+
+// Tables a reset leaves in place, so the data it removed can be restored.
+pub const RESET_KEPT_TABLES: &[&str] = &["reset_archives", "reset_requests"];
+
+/// Drops every table, along with its indexes and triggers, except
+/// `RESET_KEPT_TABLES`.
 pub fn delete_tables(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "
-      drop table if exists submissions;
-      drop table if exists events;
-      drop table if exists users;
-      ",
+    let tables = query_all(
+        conn,
+        "select name from sqlite_master where type = 'table' AND name not like 'sqlite_%';",
+        &[],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
     )?;
 
+    for table in tables
+        .iter()
+        .filter(|t| !RESET_KEPT_TABLES.contains(&t.as_str()))
+    {
+        conn.execute(format!("drop table if exists \"{}\";", table))?;
+    }
+
     Ok(())
 }
 
 pub fn add_user(conn: &Connection, near_address: String, email: String) -> Result<User> {
-    conn.execute(format!(
+    execute(
+        conn,
         "
       insert into users (near_address, email)
-      values ('{}', '{}');
+      values (?, ?);
       ",
-        near_address, email
-    ))?;
+        &[Value::String(near_address.clone()), Value::String(email)],
+    )?;
+
+    let user = get_user(conn, near_address)?;
+    search::index_user(conn, &user)?;
 
-    get_user(conn, near_address)
+    Ok(user)
 }
 
 pub fn get_user(conn: &Connection, near_address: String) -> Result<User> {
-    let mut cursor = conn
-        .prepare(format!(
-            "select * from users where near_address = '{}';",
-            near_address
-        ))?
-        .cursor();
+    query_one(
+        conn,
+        "select * from users where near_address = ?;",
+        &[Value::String(near_address)],
+        User::from_row,
+    )
+}
 
-    let row = cursor.next()?;
-    let found_user = User::from_row(row.ok_or(get_none_error())?);
-    Ok(found_user?)
+pub fn get_user_by_email(conn: &Connection, email: String) -> Result<User> {
+    query_one(
+        conn,
+        "select * from users where email = ?;",
+        &[Value::String(email)],
+        User::from_row,
+    )
 }
 
 pub fn update_user(
@@ -126,27 +265,40 @@ pub fn update_user(
     linkedin: String,
     twitter: String,
 ) -> Result<User> {
-    let _ = conn.execute(format!(
+    execute(
+        conn,
         "
         UPDATE users
-        SET first_name = '{}',
-            last_name = '{}',
-            is_student = '{}',
-            country = '{}',
-            git_handler = '{}',
-            linkedin_handler = '{}',
-            twitter_handler = '{}'
+        SET first_name = ?,
+            last_name = ?,
+            is_student = ?,
+            country = ?,
+            git_handler = ?,
+            linkedin_handler = ?,
+            twitter_handler = ?
         WHERE 
-            near_address = '{}';
+            near_address = ?;
         ",
-        first_name, last_name, is_student, country, git, linkedin, twitter, near_address
-    ));
+        &[
+            Value::String(first_name),
+            Value::String(last_name),
+            Value::Integer(is_student as i64),
+            Value::String(country),
+            Value::String(git),
+            Value::String(linkedin),
+            Value::String(twitter),
+            Value::String(near_address.clone()),
+        ],
+    )?;
+
+    let user = get_user(conn, near_address)?;
+    search::index_user(conn, &user)?;
 
-    get_user(conn, near_address)
+    Ok(user)
 }
 
 #[marine]
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct User {
     pub near_address: String,
     pub email: String,
@@ -158,6 +310,7 @@ pub struct User {
     pub linkedin_handler: String,
     pub twitter_handler: String,
     pub err_msg: String,
+    pub err_code: String,
     pub success: bool,
 }
 
@@ -174,6 +327,7 @@ impl User {
             linkedin_handler: row[7].as_string().unwrap_or_default().to_string(),
             twitter_handler: row[8].as_string().unwrap_or_default().to_string(),
             err_msg: "".to_string(),
+            err_code: "".to_string(),
             success: true,
             ..Default::default()
         };
@@ -187,6 +341,7 @@ impl User {
             Err(e) => {
                 let mut res_user: User = Default::default();
                 res_user.err_msg = e.to_string();
+                res_user.err_code = e.code.as_str().to_string();
                 res_user.success = false;
                 res_user
             }
@@ -194,9 +349,103 @@ impl User {
     }
 }
 
-// SUBMISSION
 #[marine]
 #[derive(Default)]
+pub struct UserFilter {
+    pub country: String,
+    pub is_student: i64,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct UserPage {
+    pub items: Vec<User>,
+    pub next_cursor: String,
+    pub total: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl UserPage {
+    pub fn from_res(res: Result<Page<User>>) -> UserPage {
+        match res {
+            Ok(page) => UserPage {
+                items: page.items,
+                next_cursor: page.next_cursor,
+                total: page.total,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => UserPage {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+const USER_SORT_COLUMNS: &[SortColumn] = &[
+    SortColumn {
+        name: "near_address",
+        expr: "near_address",
+    },
+    SortColumn {
+        name: "email",
+        expr: "email",
+    },
+    SortColumn {
+        name: "last_name",
+        expr: "last_name",
+    },
+    SortColumn {
+        name: "country",
+        expr: "country",
+    },
+];
+
+/// Empty `country` and a zero `is_student` match every user, `is_student`
+/// is 1 for students and 2 for everyone else.
+pub fn list_users(
+    conn: &Connection,
+    filter: UserFilter,
+    request: PageRequest,
+) -> Result<Page<User>> {
+    let mut filters = Filters::default();
+
+    if !filter.country.is_empty() {
+        filters.add("country = ?", vec![Value::String(filter.country)]);
+    }
+
+    match filter.is_student {
+        0 => {}
+        1 => filters.add("is_student = 1", vec![]),
+        2 => filters.add("coalesce(is_student, 0) = 0", vec![]),
+        _ => {
+            return Err(Error::new(
+                ErrorCode::Validation,
+                "is_student filter must be 0, 1 or 2",
+            ))
+        }
+    }
+
+    page::fetch_page(
+        conn,
+        "users",
+        "near_address",
+        USER_SORT_COLUMNS,
+        filters,
+        &request,
+        User::from_row,
+    )
+}
+
+// SUBMISSION
+#[marine]
+#[derive(Clone, Default)]
 pub struct Submission {
     pub uuid: i64,
     pub event_id: i64,
@@ -208,8 +457,11 @@ pub struct Submission {
     pub video_demo_url: String,
     pub submit_by: String,
     pub status: i64,
+    pub status_name: String,
+    pub track: String,
     pub created_by: String,
     pub err_msg: String,
+    pub err_code: String,
     pub success: bool,
 }
 
@@ -226,7 +478,18 @@ impl Submission {
             video_demo_url: row[7].as_string().unwrap_or_default().to_string(),
             submit_by: row[8].as_string().unwrap_or_default().to_string(),
             status: row[9].as_integer().unwrap_or_default(),
+            status_name: SubmissionStatus::from_code(row[9].as_integer().unwrap_or_default())
+                .map(|s| s.as_str().to_string())
+                .unwrap_or_default(),
+            // track comes with migration 15, which a database held back
+            // at an earlier version does not have yet
+            track: row
+                .get(11)
+                .and_then(|v| v.as_string())
+                .unwrap_or_default()
+                .to_string(),
             err_msg: "".to_string(),
+            err_code: "".to_string(),
             success: true,
             ..Default::default()
         };
@@ -240,6 +503,7 @@ impl Submission {
             Err(e) => {
                 let mut res_submit: Submission = Default::default();
                 res_submit.err_msg = e.to_string();
+                res_submit.err_code = e.code.as_str().to_string();
                 res_submit.success = false;
                 res_submit
             }
@@ -258,23 +522,31 @@ pub fn add_submission(
     video_url: String,
     submit_by: String,
 ) -> Result<Submission> {
-    conn.execute(format!(
+    execute(
+        conn,
         "
       insert into submissions (event_id, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, submit_by, status)
-      values ('{}', '{}', '{}', '{}', '{}', '{}', '{}', '{}', 1);
+      values (?, ?, ?, ?, ?, ?, ?, ?, ?);
       ",
-        event_id, name, description, thumbnail, git, live_url, video_url, submit_by
-    ))?;
+        &[
+            Value::Integer(event_id),
+            Value::String(name),
+            Value::String(description),
+            Value::String(thumbnail),
+            Value::String(git),
+            Value::String(live_url),
+            Value::String(video_url),
+            Value::String(submit_by),
+            Value::Integer(SubmissionStatus::Draft.code()),
+        ],
+    )?;
+
+    let new_row_id = last_insert_rowid(conn)?;
 
-    let new_row_id = conn
-        .prepare("select last_insert_rowid();")?
-        .cursor()
-        .next()?
-        .ok_or(get_none_error())?[0]
-        .as_integer()
-        .ok_or(get_none_error())?;
+    let submission = get_submission(conn, new_row_id)?;
+    search::index_submission(conn, &submission)?;
 
-    get_submission(conn, new_row_id)
+    Ok(submission)
 }
 
 pub fn update_submission(
@@ -287,47 +559,92 @@ pub fn update_submission(
     demo_url: String,
     video_url: String,
 ) -> Result<Submission> {
-    let _ = conn.execute(format!(
+    execute(
+        conn,
         "
         UPDATE submissions
         SET
-            project_name = '{}',
-            description = '{}',
-            thumbnail = '{}',
-            git_url = '{}',
-            live_demo_url = '{}',
-            video_demo_url = '{}'
+            project_name = ?,
+            description = ?,
+            thumbnail = ?,
+            git_url = ?,
+            live_demo_url = ?,
+            video_demo_url = ?
+        WHERE 
+            uuid = ?;
+        ",
+        &[
+            Value::String(name),
+            Value::String(description),
+            Value::String(thumbnail),
+            Value::String(git_url),
+            Value::String(demo_url),
+            Value::String(video_url),
+            Value::Integer(id),
+        ],
+    )?;
+
+    let submission = get_submission(conn, id)?;
+    search::index_submission(conn, &submission)?;
+
+    Ok(submission)
+}
+
+/// Moves a submission to `status`, rejecting transitions
+/// `SubmissionStatus::can_transition_to` does not allow.
+pub fn transition_submission(
+    conn: &Connection,
+    id: i64,
+    status: SubmissionStatus,
+) -> Result<Submission> {
+    let submission = get_submission(conn, id)?;
+    status::require_submission_transition(&submission, status)?;
+
+    execute(
+        conn,
+        "
+        UPDATE submissions
+        SET status = ?
         WHERE 
-            uuid = '{}';
+            uuid = ?;
         ",
-        name, description, thumbnail, git_url, demo_url, video_url, id
-    ));
+        &[Value::Integer(status.code()), Value::Integer(id)],
+    )?;
 
     get_submission(conn, id)
 }
 
-pub fn submit_submission(conn: &Connection, id: i64) -> Result<Submission> {
-    let _ = conn.execute(format!(
+/// Tracks are compared case-insensitively, so they are stored trimmed and
+/// lowercased.
+pub fn normalize_track(track: &str) -> String {
+    track.trim().to_lowercase()
+}
+
+/// Tracks group projects by theme and let review assignment prefer judges
+/// with matching expertise. An empty track clears it.
+pub fn set_submission_track(conn: &Connection, id: i64, track: String) -> Result<Submission> {
+    execute(
+        conn,
         "
         UPDATE submissions
-        SET status = 2
+        SET
+            track = ?
         WHERE 
-            uuid = '{}';
+            uuid = ?;
         ",
-        id
-    ));
+        &[Value::String(normalize_track(&track)), Value::Integer(id)],
+    )?;
 
     get_submission(conn, id)
 }
 
 pub fn get_submission(conn: &Connection, uuid: i64) -> Result<Submission> {
-    let mut cursor = conn
-        .prepare(format!("select * from submissions where uuid = {};", uuid))?
-        .cursor();
-
-    let row = cursor.next()?;
-    let found_user = Submission::from_row(row.ok_or(get_none_error())?);
-    Ok(found_user?)
+    query_one(
+        conn,
+        "select * from submissions where uuid = ?;",
+        &[Value::Integer(uuid)],
+        Submission::from_row,
+    )
 }
 
 pub fn get_user_submission_for_event(
@@ -335,48 +652,329 @@ pub fn get_user_submission_for_event(
     address: String,
     event_id: i64,
 ) -> Result<Submission> {
-    let mut cursor = conn
-        .prepare(format!(
-            "select * from submissions where submit_by = '{}' AND event_id = {};",
-            address, event_id
-        ))?
-        .cursor();
+    query_one(
+        conn,
+        "
+        select * from submissions
+        where event_id = ? AND (
+            submit_by = ?
+            OR uuid in (select submission_id from submission_team where near_address = ?)
+        );
+        ",
+        &[
+            Value::Integer(event_id),
+            Value::String(address.clone()),
+            Value::String(address),
+        ],
+        Submission::from_row,
+    )
+}
+
+pub fn get_duplicate_submission_error() -> Error {
+    Error::new(
+        ErrorCode::Conflict,
+        "User already has a submission for this event",
+    )
+}
 
-    let row = cursor.next()?;
-    let found_user = Submission::from_row(row.ok_or(get_none_error())?);
-    Ok(found_user?)
+/// Whether `address` already owns or is a team member of a submission for
+/// `event_id`.
+pub fn has_submission_for_event(conn: &Connection, address: String, event_id: i64) -> Result<bool> {
+    query_one(
+        conn,
+        "
+        select count(*) from submissions
+        where event_id = ? AND (
+            submit_by = ?
+            OR uuid in (select submission_id from submission_team where near_address = ?)
+        );
+        ",
+        &[
+            Value::Integer(event_id),
+            Value::String(address.clone()),
+            Value::String(address),
+        ],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )
 }
 
 pub fn get_submissions(conn: &Connection) -> Result<Vec<Submission>> {
-    let mut cursor = conn.prepare("select * from submissions;")?.cursor();
+    query_all(
+        conn,
+        "select * from submissions;",
+        &[],
+        Submission::from_row,
+    )
+}
 
-    let mut submissions = Vec::new();
-    while let Some(row) = cursor.next()? {
-        submissions.push(Submission::from_row(row)?);
+pub fn get_submissions_by_event(conn: &Connection, event_id: i64) -> Result<Vec<Submission>> {
+    query_all(
+        conn,
+        "select * from submissions where event_id = ?;",
+        &[Value::Integer(event_id)],
+        Submission::from_row,
+    )
+}
+
+#[marine]
+#[derive(Default)]
+pub struct SubmissionFilter {
+    pub event_id: i64,
+    pub status: i64,
+    pub submit_by: String,
+    pub created_from: String,
+    pub created_to: String,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct SubmissionPage {
+    pub items: Vec<Submission>,
+    pub next_cursor: String,
+    pub total: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl SubmissionPage {
+    pub fn from_res(res: Result<Page<Submission>>) -> SubmissionPage {
+        match res {
+            Ok(page) => SubmissionPage {
+                items: page.items,
+                next_cursor: page.next_cursor,
+                total: page.total,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => SubmissionPage {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+const SUBMISSION_SORT_COLUMNS: &[SortColumn] = &[
+    SortColumn {
+        name: "created_at",
+        expr: "created_at",
+    },
+    SortColumn {
+        name: "project_name",
+        expr: "project_name",
+    },
+    SortColumn {
+        name: "status",
+        expr: "status",
+    },
+];
+
+/// Zero and empty filter fields match everything. `created_from` and
+/// `created_to` are RFC 3339 timestamps and both bounds are inclusive.
+pub fn list_submissions(
+    conn: &Connection,
+    filter: SubmissionFilter,
+    request: PageRequest,
+) -> Result<Page<Submission>> {
+    let mut filters = Filters::default();
+
+    if filter.event_id != 0 {
+        filters.add("event_id = ?", vec![Value::Integer(filter.event_id)]);
+    }
+
+    if filter.status != 0 {
+        SubmissionStatus::from_code(filter.status)?;
+        filters.add("status = ?", vec![Value::Integer(filter.status)]);
     }
 
-    Ok(submissions)
+    if !filter.submit_by.is_empty() {
+        filters.add("submit_by = ?", vec![Value::String(filter.submit_by)]);
+    }
+
+    if !filter.created_from.is_empty() {
+        let from = schedule::parse_timestamp(&filter.created_from)?;
+        filters.add(
+            "cast(strftime('%s', created_at) as integer) >= ?",
+            vec![Value::Integer(from)],
+        );
+    }
+
+    if !filter.created_to.is_empty() {
+        let to = schedule::parse_timestamp(&filter.created_to)?;
+        filters.add(
+            "cast(strftime('%s', created_at) as integer) <= ?",
+            vec![Value::Integer(to)],
+        );
+    }
+
+    page::fetch_page(
+        conn,
+        "submissions",
+        "uuid",
+        SUBMISSION_SORT_COLUMNS,
+        filters,
+        &request,
+        Submission::from_row,
+    )
 }
 
-pub fn get_submissions_by_event(conn: &Connection, event_id: i64) -> Result<Vec<Submission>> {
-    let mut cursor = conn
-        .prepare(format!(
-            "select * from submissions where event_id = {};",
-            event_id
-        ))?
-        .cursor();
-
-    let mut submissions = Vec::new();
-    while let Some(row) = cursor.next()? {
-        submissions.push(Submission::from_row(row)?);
+// REVISIONS
+#[marine]
+#[derive(Default)]
+pub struct SubmissionRevision {
+    pub submission_id: i64,
+    pub revision: i64,
+    pub project_name: String,
+    pub description: String,
+    pub thumbnail: String,
+    pub git_url: String,
+    pub live_demo_url: String,
+    pub video_demo_url: String,
+    pub changed_fields: Vec<String>,
+    pub edited_by: String,
+    pub created_at: String,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl SubmissionRevision {
+    pub fn from_row(row: &[Value]) -> Result<SubmissionRevision> {
+        let changed_fields = row[8].as_string().unwrap_or_default();
+
+        let revision = SubmissionRevision {
+            submission_id: row[0].as_integer().ok_or(get_none_error())?,
+            revision: row[1].as_integer().ok_or(get_none_error())?,
+            project_name: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            description: row[3].as_string().ok_or(get_none_error())?.to_string(),
+            thumbnail: row[4].as_string().unwrap_or_default().to_string(),
+            git_url: row[5].as_string().unwrap_or_default().to_string(),
+            live_demo_url: row[6].as_string().unwrap_or_default().to_string(),
+            video_demo_url: row[7].as_string().unwrap_or_default().to_string(),
+            changed_fields: changed_fields
+                .split(',')
+                .filter(|f| !f.is_empty())
+                .map(|f| f.to_string())
+                .collect(),
+            edited_by: row[9].as_string().unwrap_or_default().to_string(),
+            created_at: row[10].as_string().unwrap_or_default().to_string(),
+            err_msg: "".to_string(),
+            err_code: "".to_string(),
+            success: true,
+        };
+
+        Ok(revision)
+    }
+
+    pub fn from_res(res: Result<SubmissionRevision>) -> SubmissionRevision {
+        match res {
+            Ok(v) => v,
+            Err(e) => SubmissionRevision {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
     }
 
-    Ok(submissions)
+    /// Names of the fields that differ between the two snapshots.
+    pub fn diff(&self, submission: &Submission) -> Vec<String> {
+        let fields = [
+            ("project_name", &self.project_name, &submission.project_name),
+            ("description", &self.description, &submission.description),
+            ("thumbnail", &self.thumbnail, &submission.thumbnail),
+            ("git_url", &self.git_url, &submission.git_url),
+            (
+                "live_demo_url",
+                &self.live_demo_url,
+                &submission.live_demo_url,
+            ),
+            (
+                "video_demo_url",
+                &self.video_demo_url,
+                &submission.video_demo_url,
+            ),
+        ];
+
+        fields
+            .iter()
+            .filter(|(_, old, new)| old != new)
+            .map(|(name, _, _)| name.to_string())
+            .collect()
+    }
+}
+
+/// Records the current state of `submission` as its next revision, along
+/// with the fields changed since the previous one.
+pub fn add_submission_revision(
+    conn: &Connection,
+    submission: &Submission,
+    edited_by: String,
+) -> Result<SubmissionRevision> {
+    let changed_fields = match get_history(conn, submission.uuid)?.last() {
+        Some(previous) => previous.diff(submission),
+        None => Vec::new(),
+    };
+
+    execute(
+        conn,
+        "
+      insert into submission_revisions (submission_id, revision, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, changed_fields, edited_by)
+      values (?, (select coalesce(max(revision), 0) + 1 from submission_revisions where submission_id = ?), ?, ?, ?, ?, ?, ?, ?, ?);
+      ",
+        &[
+            Value::Integer(submission.uuid),
+            Value::Integer(submission.uuid),
+            Value::String(submission.project_name.clone()),
+            Value::String(submission.description.clone()),
+            Value::String(submission.thumbnail.clone()),
+            Value::String(submission.git_url.clone()),
+            Value::String(submission.live_demo_url.clone()),
+            Value::String(submission.video_demo_url.clone()),
+            Value::String(changed_fields.join(",")),
+            Value::String(edited_by),
+        ],
+    )?;
+
+    query_one(
+        conn,
+        "select * from submission_revisions where submission_id = ? order by revision desc limit 1;",
+        &[Value::Integer(submission.uuid)],
+        SubmissionRevision::from_row,
+    )
+}
+
+/// Every revision of a submission, oldest first.
+pub fn get_history(conn: &Connection, submission_id: i64) -> Result<Vec<SubmissionRevision>> {
+    query_all(
+        conn,
+        "select * from submission_revisions where submission_id = ? order by revision;",
+        &[Value::Integer(submission_id)],
+        SubmissionRevision::from_row,
+    )
+}
+
+pub fn get_revision(
+    conn: &Connection,
+    submission_id: i64,
+    revision: i64,
+) -> Result<SubmissionRevision> {
+    query_one(
+        conn,
+        "select * from submission_revisions where submission_id = ? AND revision = ?;",
+        &[Value::Integer(submission_id), Value::Integer(revision)],
+        SubmissionRevision::from_row,
+    )
 }
 
 // EVENTS
 #[marine]
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Event {
     pub id: i64,
     pub title: String,
@@ -385,7 +983,11 @@ pub struct Event {
     pub end_date: String,
     pub logo: String,
     pub status: i64,
+    pub max_team_size: i64,
+    pub grace_period_minutes: i64,
+    pub normalize_scores: bool,
     pub err_msg: String,
+    pub err_code: String,
     pub success: bool,
 }
 
@@ -393,13 +995,18 @@ impl Event {
     pub fn from_row(row: &[Value]) -> Result<Event> {
         let event = Event {
             id: row[0].as_integer().ok_or(get_none_error())?,
-            title: row[1].as_string().ok_or(get_none_error())?.to_string(),
-            event_type: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            event_type: row[1].as_string().ok_or(get_none_error())?.to_string(),
+            title: row[2].as_string().ok_or(get_none_error())?.to_string(),
             start_date: row[3].as_string().unwrap_or_default().to_string(),
             end_date: row[4].as_string().unwrap_or_default().to_string(),
             logo: row[5].as_string().unwrap_or_default().to_string(),
             status: row[6].as_integer().ok_or(get_none_error())?,
+            max_team_size: row[7].as_integer().unwrap_or_default(),
+            grace_period_minutes: row[8].as_integer().unwrap_or_default(),
+            // added by migration 13, see Submission::from_row
+            normalize_scores: row.get(11).and_then(|v| v.as_integer()).unwrap_or(1) != 0,
             err_msg: "".to_string(),
+            err_code: "".to_string(),
             success: true,
             ..Default::default()
         };
@@ -413,6 +1020,7 @@ impl Event {
             Err(e) => {
                 let mut res_event: Event = Default::default();
                 res_event.err_msg = e.to_string();
+                res_event.err_code = e.code.as_str().to_string();
                 res_event.success = false;
                 res_event
             }
@@ -428,19 +1036,10 @@ pub fn add_event(
     end_date: String,
     logo: String,
 ) -> Result<Event> {
-    conn.execute(format!(
-        "
-      insert into events (title, type, start_date, end_date, logo, status)
-      values ('{}', '{}', '{}', '{}', '{}', 1);
-      ",
-        title, event_type, start_date, end_date, logo
-    ))?;
+    schedule::validate_event_dates(&start_date, &end_date)?;
 
     log::info!(
-        "
-    insert into events (title, type, start_date, end_date, logo, status)
-    values ('{}', '{}', '{}', '{}', '{}', 1);
-    ",
+        "insert into events: title={:?} type={:?} start_date={:?} end_date={:?} logo={:?}",
         title,
         event_type,
         start_date,
@@ -448,13 +1047,24 @@ pub fn add_event(
         logo
     );
 
-    let new_row_id = conn
-        .prepare("select last_insert_rowid();")?
-        .cursor()
-        .next()?
-        .ok_or(get_none_error())?[0]
-        .as_integer()
-        .ok_or(get_none_error())?;
+    execute(
+        conn,
+        "
+      insert into events (title, type, start_date, end_date, logo, status)
+      values (?, ?, ?, ?, ?, ?);
+      ",
+        &[
+            Value::String(title),
+            Value::String(event_type),
+            Value::String(start_date.clone()),
+            Value::String(end_date.clone()),
+            Value::String(logo),
+            Value::Integer(EventPhase::Draft.code()),
+        ],
+    )?;
+
+    let new_row_id = last_insert_rowid(conn)?;
+    set_event_timestamps(conn, new_row_id, &start_date, &end_date)?;
 
     get_event(conn, new_row_id)
 }
@@ -468,69 +1078,543 @@ pub fn update_event(
     end_date: String,
     logo: String,
 ) -> Result<Event> {
-    let _ = conn.execute(format!(
+    schedule::validate_event_dates(&start_date, &end_date)?;
+
+    execute(
+        conn,
         "
         UPDATE events
         SET
-            title = '{}',
-            type = '{}',
-            start_date = '{}',
-            end_date = '{}',
-            logo = '{}'
+            title = ?,
+            type = ?,
+            start_date = ?,
+            end_date = ?,
+            logo = ?
         WHERE 
-            id = '{}';
+            id = ?;
         ",
-        title, event_type, start_date, end_date, logo, id
-    ));
+        &[
+            Value::String(title),
+            Value::String(event_type),
+            Value::String(start_date.clone()),
+            Value::String(end_date.clone()),
+            Value::String(logo),
+            Value::Integer(id),
+        ],
+    )?;
+    set_event_timestamps(conn, id, &start_date, &end_date)?;
 
     get_event(conn, id)
 }
 
-pub fn close_event(conn: &Connection, id: i64) -> Result<Event> {
-    let _ = conn.execute(format!(
+/// Moves an event to `phase`, rejecting transitions the lifecycle does not
+/// allow.
+pub fn transition_event(conn: &Connection, id: i64, phase: EventPhase) -> Result<Event> {
+    let event = get_event(conn, id)?;
+    status::require_event_transition(&event, phase)?;
+
+    execute(
+        conn,
         "
         UPDATE events
         SET
-            status = 2
+            status = ?
         WHERE 
-            id = '{}';
+            id = ?;
         ",
-        id
-    ));
+        &[Value::Integer(phase.code()), Value::Integer(id)],
+    )?;
+
+    if phase == EventPhase::ResultsPublished {
+        leaderboard::freeze(conn, id)?;
+    }
 
     get_event(conn, id)
 }
 
-pub fn get_event(conn: &Connection, id: i64) -> Result<Event> {
-    let mut cursor = conn
-        .prepare(format!("select * from events where id = {};", id))?
-        .cursor();
+pub fn validate_team_size(max_team_size: i64) -> Result<()> {
+    if max_team_size < 1 {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Team size must be at least 1",
+        ));
+    }
 
-    let row = cursor.next()?;
-    let found_user = Event::from_row(row.ok_or(get_none_error())?);
-    Ok(found_user?)
+    Ok(())
 }
 
-pub fn get_live_events(conn: &Connection) -> Result<Vec<Event>> {
-    let mut cursor = conn
-        .prepare("select * from events where status = 1;")?
-        .cursor();
+pub fn set_event_team_size(conn: &Connection, id: i64, max_team_size: i64) -> Result<Event> {
+    validate_team_size(max_team_size)?;
 
-    let mut events = Vec::new();
-    while let Some(row) = cursor.next()? {
-        events.push(Event::from_row(row)?);
+    execute(
+        conn,
+        "
+        UPDATE events
+        SET
+            max_team_size = ?
+        WHERE 
+            id = ?;
+        ",
+        &[Value::Integer(max_team_size), Value::Integer(id)],
+    )?;
+
+    get_event(conn, id)
+}
+
+pub fn validate_grace_period(minutes: i64) -> Result<()> {
+    if minutes < 0 {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Grace period cannot be negative",
+        ));
     }
 
-    Ok(events)
+    Ok(())
+}
+
+pub fn set_event_grace_period(conn: &Connection, id: i64, minutes: i64) -> Result<Event> {
+    validate_grace_period(minutes)?;
+
+    execute(
+        conn,
+        "
+        UPDATE events
+        SET
+            grace_period_minutes = ?
+        WHERE 
+            id = ?;
+        ",
+        &[Value::Integer(minutes), Value::Integer(id)],
+    )?;
+
+    get_event(conn, id)
+}
+
+/// Scoring settings are fixed once the results are out.
+pub fn require_unpublished(event: &Event) -> Result<()> {
+    if leaderboard::is_published(EventPhase::from_code(event.status)?) {
+        return Err(Error::new(
+            ErrorCode::Locked,
+            "Results are already published",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Chooses whether the leaderboard ranks by per-judge z-scores or by raw
+/// weighted totals. Fixed once results are published.
+pub fn set_event_score_normalization(conn: &Connection, id: i64, enabled: bool) -> Result<Event> {
+    require_unpublished(&get_event(conn, id)?)?;
+
+    execute(
+        conn,
+        "
+        UPDATE events
+        SET
+            normalize_scores = ?
+        WHERE 
+            id = ?;
+        ",
+        &[Value::Integer(enabled as i64), Value::Integer(id)],
+    )?;
+
+    get_event(conn, id)
+}
+
+pub fn get_event(conn: &Connection, id: i64) -> Result<Event> {
+    query_one(
+        conn,
+        "select * from events where id = ?;",
+        &[Value::Integer(id)],
+        Event::from_row,
+    )
+}
+
+pub fn get_live_events(conn: &Connection) -> Result<Vec<Event>> {
+    query_all(
+        conn,
+        "select * from events where status >= ? AND status <= ?;",
+        &[
+            Value::Integer(EventPhase::RegistrationOpen.code()),
+            Value::Integer(EventPhase::ResultsPublished.code()),
+        ],
+        Event::from_row,
+    )
 }
 
 pub fn get_events(conn: &Connection) -> Result<Vec<Event>> {
-    let mut cursor = conn.prepare("select * from events;")?.cursor();
+    query_all(conn, "select * from events;", &[], Event::from_row)
+}
 
-    let mut events = Vec::new();
-    while let Some(row) = cursor.next()? {
-        events.push(Event::from_row(row)?);
+#[marine]
+#[derive(Default)]
+pub struct EventFilter {
+    pub status: i64,
+    pub event_type: String,
+    pub starts_from: String,
+    pub starts_to: String,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct EventPage {
+    pub items: Vec<Event>,
+    pub next_cursor: String,
+    pub total: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl EventPage {
+    pub fn from_res(res: Result<Page<Event>>) -> EventPage {
+        match res {
+            Ok(page) => EventPage {
+                items: page.items,
+                next_cursor: page.next_cursor,
+                total: page.total,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => EventPage {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
     }
+}
+
+const EVENT_SORT_COLUMNS: &[SortColumn] = &[
+    SortColumn {
+        name: "start_date",
+        expr: "starts_at",
+    },
+    SortColumn {
+        name: "end_date",
+        expr: "ends_at",
+    },
+    SortColumn {
+        name: "title",
+        expr: "title",
+    },
+    SortColumn {
+        name: "status",
+        expr: "status",
+    },
+];
+
+/// Zero and empty filter fields match everything. `starts_from` and
+/// `starts_to` are RFC 3339 timestamps and both bounds are inclusive.
+pub fn list_events(
+    conn: &Connection,
+    filter: EventFilter,
+    request: PageRequest,
+) -> Result<Page<Event>> {
+    let mut filters = Filters::default();
+
+    if filter.status != 0 {
+        EventPhase::from_code(filter.status)?;
+        filters.add("status = ?", vec![Value::Integer(filter.status)]);
+    }
+
+    if !filter.event_type.is_empty() {
+        filters.add("type = ?", vec![Value::String(filter.event_type)]);
+    }
+
+    if !filter.starts_from.is_empty() {
+        let from = schedule::parse_timestamp(&filter.starts_from)?;
+        filters.add("starts_at >= ?", vec![Value::Integer(from)]);
+    }
+
+    if !filter.starts_to.is_empty() {
+        let to = schedule::parse_timestamp(&filter.starts_to)?;
+        filters.add("starts_at <= ?", vec![Value::Integer(to)]);
+    }
+
+    page::fetch_page(
+        conn,
+        "events",
+        "id",
+        EVENT_SORT_COLUMNS,
+        filters,
+        &request,
+        Event::from_row,
+    )
+}
+
+/// Keeps the integer `starts_at`/`ends_at` columns, used for filtering and
+/// sorting, in step with the RFC 3339 dates. Dates that do not parse are
+/// stored as null.
+pub fn set_event_timestamps(
+    conn: &Connection,
+    id: i64,
+    start_date: &str,
+    end_date: &str,
+) -> Result<()> {
+    let to_value = |date: &str| {
+        schedule::parse_timestamp(date)
+            .map(Value::Integer)
+            .unwrap_or(Value::Null)
+    };
+
+    execute(
+        conn,
+        "UPDATE events SET starts_at = ?, ends_at = ? WHERE id = ?;",
+        &[to_value(start_date), to_value(end_date), Value::Integer(id)],
+    )
+}
+
+// TEAM
+#[marine]
+#[derive(Default)]
+pub struct TeamMember {
+    pub submission_id: i64,
+    pub near_address: String,
+    pub created_at: String,
+}
+
+impl TeamMember {
+    pub fn from_row(row: &[Value]) -> Result<TeamMember> {
+        let member = TeamMember {
+            submission_id: row[0].as_integer().ok_or(get_none_error())?,
+            near_address: row[1].as_string().ok_or(get_none_error())?.to_string(),
+            created_at: row[2].as_string().unwrap_or_default().to_string(),
+        };
+
+        Ok(member)
+    }
+}
+
+/// Team members of a submission, not counting its owner (`submit_by`).
+pub fn get_submission_team(conn: &Connection, submission_id: i64) -> Result<Vec<TeamMember>> {
+    query_all(
+        conn,
+        "select submission_id, near_address, created_at from submission_team where submission_id = ?;",
+        &[Value::Integer(submission_id)],
+        TeamMember::from_row,
+    )
+}
+
+pub fn is_team_member(conn: &Connection, submission_id: i64, near_address: String) -> Result<bool> {
+    query_one(
+        conn,
+        "select count(*) from submission_team where submission_id = ? AND near_address = ?;",
+        &[Value::Integer(submission_id), Value::String(near_address)],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )
+}
+
+/// Adds `near_address` to the team, enforcing the event's team size (owner
+/// included) and the one submission per user per event rule.
+pub fn add_team_member(conn: &Connection, submission_id: i64, near_address: String) -> Result<()> {
+    let submission = get_submission(conn, submission_id)?;
+    let event = get_event(conn, submission.event_id)?;
+    get_user(conn, near_address.clone())?;
+
+    if has_submission_for_event(conn, near_address.clone(), submission.event_id)? {
+        return Err(get_duplicate_submission_error());
+    }
+
+    let team_size = get_submission_team(conn, submission_id)?.len() as i64 + 1;
+    if team_size >= event.max_team_size {
+        return Err(Error::new(
+            ErrorCode::Conflict,
+            format!(
+                "Team is full, this event allows {} members",
+                event.max_team_size
+            ),
+        ));
+    }
+
+    execute(
+        conn,
+        "insert into submission_team (submission_id, near_address) values (?, ?);",
+        &[Value::Integer(submission_id), Value::String(near_address)],
+    )
+}
+
+pub fn remove_team_member(
+    conn: &Connection,
+    submission_id: i64,
+    near_address: String,
+) -> Result<()> {
+    if !is_team_member(conn, submission_id, near_address.clone())? {
+        return Err(get_none_error());
+    }
+
+    execute(
+        conn,
+        "delete from submission_team where submission_id = ? AND near_address = ?;",
+        &[Value::Integer(submission_id), Value::String(near_address)],
+    )
+}
+
+// INVITATION
+pub const INVITATION_PENDING: i64 = 1;
+pub const INVITATION_ACCEPTED: i64 = 2;
+pub const INVITATION_DECLINED: i64 = 3;
+pub const INVITATION_REVOKED: i64 = 4;
+
+// How long an invitation stays open.
+const INVITATION_TTL: &str = "+7 days";
+
+#[marine]
+#[derive(Default)]
+pub struct Invitation {
+    pub id: i64,
+    pub submission_id: i64,
+    pub invitee: String,
+    pub invited_by: String,
+    pub status: i64,
+    pub expires_at: String,
+    pub created_at: String,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Invitation {
+    pub fn from_row(row: &[Value]) -> Result<Invitation> {
+        let invitation = Invitation {
+            id: row[0].as_integer().ok_or(get_none_error())?,
+            submission_id: row[1].as_integer().ok_or(get_none_error())?,
+            invitee: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            invited_by: row[3].as_string().ok_or(get_none_error())?.to_string(),
+            status: row[4].as_integer().ok_or(get_none_error())?,
+            expires_at: row[5].as_string().unwrap_or_default().to_string(),
+            created_at: row[6].as_string().unwrap_or_default().to_string(),
+            err_msg: "".to_string(),
+            err_code: "".to_string(),
+            success: true,
+        };
+
+        Ok(invitation)
+    }
+
+    pub fn from_res(res: Result<Invitation>) -> Invitation {
+        match res {
+            Ok(v) => v,
+            Err(e) => Invitation {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Invites the user registered under `invitee`, given either as a NEAR
+/// address or an email.
+pub fn add_invitation(
+    conn: &Connection,
+    submission_id: i64,
+    invitee: String,
+    invited_by: String,
+) -> Result<Invitation> {
+    let user = get_user(conn, invitee.clone()).or_else(|_| get_user_by_email(conn, invitee))?;
+
+    if user.near_address == invited_by
+        || is_team_member(conn, submission_id, user.near_address.clone())?
+    {
+        return Err(Error::new(
+            ErrorCode::Conflict,
+            "User is already in the team",
+        ));
+    }
+
+    execute(
+        conn,
+        "
+      insert into team_invitations (submission_id, invitee, invited_by, status, expires_at)
+      values (?, ?, ?, ?, datetime('now', ?));
+      ",
+        &[
+            Value::Integer(submission_id),
+            Value::String(user.near_address),
+            Value::String(invited_by),
+            Value::Integer(INVITATION_PENDING),
+            Value::String(INVITATION_TTL.to_string()),
+        ],
+    )?;
+
+    let new_row_id = last_insert_rowid(conn)?;
+
+    get_invitation(conn, new_row_id)
+}
+
+pub fn get_invitation(conn: &Connection, id: i64) -> Result<Invitation> {
+    query_one(
+        conn,
+        "select * from team_invitations where id = ?;",
+        &[Value::Integer(id)],
+        Invitation::from_row,
+    )
+}
+
+/// Pending invitations for `invitee` that have not expired yet.
+pub fn get_pending_invitations(conn: &Connection, invitee: String) -> Result<Vec<Invitation>> {
+    query_all(
+        conn,
+        "
+        select * from team_invitations
+        where invitee = ? AND status = ? AND expires_at > datetime('now');
+        ",
+        &[Value::String(invitee), Value::Integer(INVITATION_PENDING)],
+        Invitation::from_row,
+    )
+}
+
+/// Moves a pending, unexpired invitation addressed to `invitee` to `status`.
+/// Accepting also joins the team, subject to the usual team rules.
+pub fn answer_invitation(
+    conn: &Connection,
+    id: i64,
+    invitee: String,
+    status: i64,
+) -> Result<Invitation> {
+    let invitation = query_one(
+        conn,
+        "
+        select * from team_invitations
+        where id = ? AND invitee = ? AND status = ? AND expires_at > datetime('now');
+        ",
+        &[
+            Value::Integer(id),
+            Value::String(invitee),
+            Value::Integer(INVITATION_PENDING),
+        ],
+        Invitation::from_row,
+    )
+    .map_err(|_| Error::new(ErrorCode::NotFound, "No pending invitation"))?;
+
+    if status == INVITATION_ACCEPTED {
+        add_team_member(conn, invitation.submission_id, invitation.invitee.clone())?;
+    }
+
+    execute(
+        conn,
+        "UPDATE team_invitations SET status = ? WHERE id = ?;",
+        &[Value::Integer(status), Value::Integer(id)],
+    )?;
+
+    get_invitation(conn, id)
+}
 
-    Ok(events)
+/// Revokes every pending invitation of a submission once it leaves the draft
+/// state, by being submitted or withdrawn.
+pub fn revoke_invitations(conn: &Connection, submission_id: i64) -> Result<()> {
+    execute(
+        conn,
+        "UPDATE team_invitations SET status = ? WHERE submission_id = ? AND status = ?;",
+        &[
+            Value::Integer(INVITATION_REVOKED),
+            Value::Integer(submission_id),
+            Value::Integer(INVITATION_PENDING),
+        ],
+    )
 }
@@ -0,0 +1,73 @@
+use std::fmt;
+
+use marine_sqlite_connector as sqlite;
+
+// SQLite primary result code for constraint violations (unique, foreign key).
+const SQLITE_CONSTRAINT: isize = 19;
+
+/// Stable error codes returned to callers in the `err_code` field of every
+/// result record. Clients should branch on these rather than on `err_msg`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCode {
+    NotFound,
+    Unauthorized,
+    Validation,
+    Conflict,
+    InvalidState,
+    Locked,
+    NotStarted,
+    DeadlinePassed,
+    DbError,
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::Validation => "VALIDATION",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::InvalidState => "INVALID_STATE",
+            ErrorCode::Locked => "LOCKED",
+            ErrorCode::NotStarted => "NOT_STARTED",
+            ErrorCode::DeadlinePassed => "DEADLINE_PASSED",
+            ErrorCode::DbError => "DB_ERROR",
+            ErrorCode::Internal => "INTERNAL",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new<T: Into<String>>(code: ErrorCode, message: T) -> Error {
+        Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<sqlite::Error> for Error {
+    fn from(e: sqlite::Error) -> Error {
+        let code = match e.code {
+            Some(SQLITE_CONSTRAINT) => ErrorCode::Conflict,
+            _ => ErrorCode::DbError,
+        };
+
+        Error::new(code, e.to_string())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
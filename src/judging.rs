@@ -0,0 +1,747 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::conflicts;
+use crate::db::{self, get_none_error, Submission};
+use crate::error::{Error, ErrorCode, Result};
+use crate::status::{EventPhase, SubmissionStatus};
+
+// JUDGES
+#[marine]
+#[derive(Default)]
+pub struct EventJudge {
+    pub event_id: i64,
+    pub near_address: String,
+    pub assigned_by: String,
+    pub created_at: String,
+}
+
+impl EventJudge {
+    pub fn from_row(row: &[Value]) -> Result<EventJudge> {
+        Ok(EventJudge {
+            event_id: row[0].as_integer().ok_or(get_none_error())?,
+            near_address: row[1].as_string().ok_or(get_none_error())?.to_string(),
+            assigned_by: row[2].as_string().unwrap_or_default().to_string(),
+            created_at: row[3].as_string().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+pub fn add_event_judge(
+    conn: &Connection,
+    event_id: i64,
+    near_address: String,
+    assigned_by: String,
+) -> Result<()> {
+    db::get_event(conn, event_id)?;
+    db::get_user(conn, near_address.clone())?;
+
+    db::execute(
+        conn,
+        "insert into event_judges (event_id, near_address, assigned_by) values (?, ?, ?);",
+        &[
+            Value::Integer(event_id),
+            Value::String(near_address),
+            Value::String(assigned_by),
+        ],
+    )
+    .map_err(|e| match e.code {
+        ErrorCode::Conflict => Error::new(
+            ErrorCode::Conflict,
+            "User is already a judge for this event",
+        ),
+        _ => e,
+    })
+}
+
+/// Also drops the judge's assignments in the event that have no review yet;
+/// reviews already written stay and still count.
+pub fn remove_event_judge(conn: &Connection, event_id: i64, near_address: String) -> Result<()> {
+    if !is_event_judge(conn, event_id, near_address.clone())? {
+        return Err(get_none_error());
+    }
+
+    db::execute(
+        conn,
+        "delete from event_judges where event_id = ? AND near_address = ?;",
+        &[Value::Integer(event_id), Value::String(near_address.clone())],
+    )?;
+
+    db::execute(
+        conn,
+        "
+        delete from review_assignments
+        where event_id = ? AND judge = ?
+        AND not exists (
+          select 1 from reviews r
+          where r.submission_id = review_assignments.submission_id AND r.judge = review_assignments.judge
+        );
+        ",
+        &[Value::Integer(event_id), Value::String(near_address)],
+    )
+}
+
+pub fn get_event_judges(conn: &Connection, event_id: i64) -> Result<Vec<EventJudge>> {
+    db::query_all(
+        conn,
+        "select * from event_judges where event_id = ? order by created_at, near_address;",
+        &[Value::Integer(event_id)],
+        EventJudge::from_row,
+    )
+}
+
+pub fn is_event_judge(conn: &Connection, event_id: i64, near_address: String) -> Result<bool> {
+    db::query_one(
+        conn,
+        "select count(*) from event_judges where event_id = ? AND near_address = ?;",
+        &[Value::Integer(event_id), Value::String(near_address)],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )
+}
+
+/// Submitted projects of an event, the set its judges score.
+pub fn get_judging_submissions(conn: &Connection, event_id: i64) -> Result<Vec<Submission>> {
+    db::query_all(
+        conn,
+        "select * from submissions where event_id = ? AND status = ? order by uuid;",
+        &[
+            Value::Integer(event_id),
+            Value::Integer(SubmissionStatus::Submitted.code()),
+        ],
+        Submission::from_row,
+    )
+}
+
+// RUBRIC
+#[marine]
+#[derive(Default)]
+pub struct Criterion {
+    pub id: i64,
+    pub event_id: i64,
+    pub name: String,
+    pub description: String,
+    pub weight: f64,
+    pub max_score: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Criterion {
+    pub fn from_row(row: &[Value]) -> Result<Criterion> {
+        Ok(Criterion {
+            id: row[0].as_integer().ok_or(get_none_error())?,
+            event_id: row[1].as_integer().ok_or(get_none_error())?,
+            name: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            description: row[3].as_string().unwrap_or_default().to_string(),
+            weight: row[4].as_float().unwrap_or_default(),
+            max_score: row[5].as_integer().unwrap_or_default(),
+            err_msg: "".to_string(),
+            err_code: "".to_string(),
+            success: true,
+        })
+    }
+
+    pub fn from_res(res: Result<Criterion>) -> Criterion {
+        match res {
+            Ok(v) => v,
+            Err(e) => Criterion {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+fn get_rubric_locked_error() -> Error {
+    Error::new(
+        ErrorCode::Locked,
+        "Rubric cannot change once scoring has started",
+    )
+}
+
+fn has_reviews(conn: &Connection, event_id: i64) -> Result<bool> {
+    db::query_one(
+        conn,
+        "
+        select count(*) from reviews r
+        join submissions s on s.uuid = r.submission_id
+        where s.event_id = ?;
+        ",
+        &[Value::Integer(event_id)],
+        |row| Ok(row[0].as_integer().unwrap_or_default() > 0),
+    )
+}
+
+pub fn add_criterion(
+    conn: &Connection,
+    event_id: i64,
+    name: String,
+    description: String,
+    weight: f64,
+    max_score: i64,
+) -> Result<Criterion> {
+    db::get_event(conn, event_id)?;
+
+    if name.trim().is_empty() {
+        return Err(Error::new(ErrorCode::Validation, "Criterion needs a name"));
+    }
+    if !weight.is_finite() || weight <= 0.0 {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Criterion weight must be greater than 0",
+        ));
+    }
+    if max_score < 1 {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Criterion max_score must be at least 1",
+        ));
+    }
+    if has_reviews(conn, event_id)? {
+        return Err(get_rubric_locked_error());
+    }
+
+    db::execute(
+        conn,
+        "insert into rubric_criteria (event_id, name, description, weight, max_score) values (?, ?, ?, ?, ?);",
+        &[
+            Value::Integer(event_id),
+            Value::String(name),
+            Value::String(description),
+            Value::Float(weight),
+            Value::Integer(max_score),
+        ],
+    )?;
+
+    let id = db::last_insert_rowid(conn)?;
+    get_criterion(conn, id)
+}
+
+pub fn remove_criterion(conn: &Connection, id: i64) -> Result<()> {
+    let criterion = get_criterion(conn, id)?;
+    if has_reviews(conn, criterion.event_id)? {
+        return Err(get_rubric_locked_error());
+    }
+
+    db::execute(
+        conn,
+        "delete from rubric_criteria where id = ?;",
+        &[Value::Integer(id)],
+    )
+}
+
+pub fn get_criterion(conn: &Connection, id: i64) -> Result<Criterion> {
+    db::query_one(
+        conn,
+        "select * from rubric_criteria where id = ?;",
+        &[Value::Integer(id)],
+        Criterion::from_row,
+    )
+}
+
+pub fn get_rubric(conn: &Connection, event_id: i64) -> Result<Vec<Criterion>> {
+    db::query_all(
+        conn,
+        "select * from rubric_criteria where event_id = ? order by id;",
+        &[Value::Integer(event_id)],
+        Criterion::from_row,
+    )
+}
+
+// SCORES
+#[marine]
+#[derive(Default, Clone)]
+pub struct CriterionScore {
+    pub criterion_id: i64,
+    pub score: i64,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct Review {
+    pub submission_id: i64,
+    pub judge: String,
+    pub comment: String,
+    pub total: f64,
+    pub scores: Vec<CriterionScore>,
+    pub updated_at: String,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Review {
+    pub fn from_row(row: &[Value]) -> Result<Review> {
+        Ok(Review {
+            submission_id: row[0].as_integer().ok_or(get_none_error())?,
+            judge: row[1].as_string().ok_or(get_none_error())?.to_string(),
+            comment: row[2].as_string().unwrap_or_default().to_string(),
+            total: row[3].as_float().unwrap_or_default(),
+            updated_at: row[5].as_string().unwrap_or_default().to_string(),
+            err_msg: "".to_string(),
+            err_code: "".to_string(),
+            success: true,
+            ..Default::default()
+        })
+    }
+
+    pub fn from_res(res: Result<Review>) -> Review {
+        match res {
+            Ok(v) => v,
+            Err(e) => Review {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Weighted total on a 0 to 100 scale: each criterion contributes its score
+/// as a fraction of `max_score`, weighted by its share of the rubric weight.
+/// Fails unless `scores` covers every criterion exactly once and in range.
+pub fn weighted_total(rubric: &[Criterion], scores: &[CriterionScore]) -> Result<f64> {
+    if rubric.is_empty() {
+        return Err(Error::new(
+            ErrorCode::InvalidState,
+            "Event has no rubric to score against",
+        ));
+    }
+
+    for score in scores {
+        if !rubric.iter().any(|c| c.id == score.criterion_id) {
+            return Err(Error::new(
+                ErrorCode::Validation,
+                format!(
+                    "Criterion {} is not part of this rubric",
+                    score.criterion_id
+                ),
+            ));
+        }
+    }
+
+    let mut total = 0.0;
+    let mut weights = 0.0;
+    for criterion in rubric {
+        let mut matching = scores.iter().filter(|s| s.criterion_id == criterion.id);
+        let score = match (matching.next(), matching.next()) {
+            (Some(score), None) => score.score,
+            (None, _) => {
+                return Err(Error::new(
+                    ErrorCode::Validation,
+                    format!("Missing score for {}", criterion.name),
+                ))
+            }
+            (Some(_), Some(_)) => {
+                return Err(Error::new(
+                    ErrorCode::Validation,
+                    format!("{} is scored more than once", criterion.name),
+                ))
+            }
+        };
+
+        if score < 0 || score > criterion.max_score {
+            return Err(Error::new(
+                ErrorCode::Validation,
+                format!(
+                    "Score for {} must be between 0 and {}",
+                    criterion.name, criterion.max_score
+                ),
+            ));
+        }
+
+        total += score as f64 / criterion.max_score as f64 * criterion.weight;
+        weights += criterion.weight;
+    }
+
+    Ok(total / weights * 100.0)
+}
+
+/// Records `judge`'s scores for a submission, replacing any earlier review
+/// by the same judge. Only submitted projects of an event in judging can be
+/// scored, and only by a judge without a conflict of interest.
+pub fn score_submission(
+    conn: &Connection,
+    submission_id: i64,
+    judge: String,
+    scores: Vec<CriterionScore>,
+    comment: String,
+) -> Result<Review> {
+    let submission = db::get_submission(conn, submission_id)?;
+    let event = db::get_event(conn, submission.event_id)?;
+
+    if EventPhase::from_code(event.status)? != EventPhase::Judging {
+        return Err(Error::new(
+            ErrorCode::InvalidState,
+            "Event is not in judging",
+        ));
+    }
+    if SubmissionStatus::from_code(submission.status)? != SubmissionStatus::Submitted {
+        return Err(Error::new(
+            ErrorCode::InvalidState,
+            "Only submitted projects can be scored",
+        ));
+    }
+
+    conflicts::require_independent(conn, &judge, &submission)?;
+
+    let rubric = get_rubric(conn, event.id)?;
+    let total = weighted_total(&rubric, &scores)?;
+
+    db::execute(
+        conn,
+        "
+        insert into reviews (submission_id, judge, comment, total)
+        values (?, ?, ?, ?)
+        on conflict (submission_id, judge) do update
+        set comment = excluded.comment, total = excluded.total, updated_at = CURRENT_TIMESTAMP;
+        ",
+        &[
+            Value::Integer(submission_id),
+            Value::String(judge.clone()),
+            Value::String(comment),
+            Value::Float(total),
+        ],
+    )?;
+
+    db::execute(
+        conn,
+        "delete from review_scores where submission_id = ? AND judge = ?;",
+        &[Value::Integer(submission_id), Value::String(judge.clone())],
+    )?;
+
+    for score in scores {
+        db::execute(
+            conn,
+            "insert into review_scores (submission_id, judge, criterion_id, score) values (?, ?, ?, ?);",
+            &[
+                Value::Integer(submission_id),
+                Value::String(judge.clone()),
+                Value::Integer(score.criterion_id),
+                Value::Integer(score.score),
+            ],
+        )?;
+    }
+
+    get_review(conn, submission_id, judge)
+}
+
+fn with_scores(conn: &Connection, mut review: Review) -> Result<Review> {
+    review.scores = db::query_all(
+        conn,
+        "select criterion_id, score from review_scores where submission_id = ? AND judge = ? order by criterion_id;",
+        &[
+            Value::Integer(review.submission_id),
+            Value::String(review.judge.clone()),
+        ],
+        |row| {
+            Ok(CriterionScore {
+                criterion_id: row[0].as_integer().ok_or(get_none_error())?,
+                score: row[1].as_integer().ok_or(get_none_error())?,
+            })
+        },
+    )?;
+
+    Ok(review)
+}
+
+pub fn get_review(conn: &Connection, submission_id: i64, judge: String) -> Result<Review> {
+    let review = db::query_one(
+        conn,
+        "select * from reviews where submission_id = ? AND judge = ?;",
+        &[Value::Integer(submission_id), Value::String(judge)],
+        Review::from_row,
+    )?;
+
+    with_scores(conn, review)
+}
+
+pub fn get_reviews(conn: &Connection, submission_id: i64) -> Result<Vec<Review>> {
+    db::query_all(
+        conn,
+        "select * from reviews where submission_id = ? order by judge;",
+        &[Value::Integer(submission_id)],
+        Review::from_row,
+    )?
+    .into_iter()
+    .map(|review| with_scores(conn, review))
+    .collect()
+}
+
+// EXPERTISE
+/// Replaces the tracks `near_address` is an expert in for `event_id`.
+pub fn set_judge_tracks(
+    conn: &Connection,
+    event_id: i64,
+    near_address: String,
+    tracks: Vec<String>,
+) -> Result<()> {
+    if !is_event_judge(conn, event_id, near_address.clone())? {
+        return Err(Error::new(
+            ErrorCode::NotFound,
+            "User is not a judge for this event",
+        ));
+    }
+
+    let mut tracks: Vec<String> = tracks
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tracks.sort();
+    tracks.dedup();
+
+    db::execute(
+        conn,
+        "delete from judge_tracks where event_id = ? AND near_address = ?;",
+        &[
+            Value::Integer(event_id),
+            Value::String(near_address.clone()),
+        ],
+    )?;
+
+    for track in tracks {
+        db::execute(
+            conn,
+            "insert into judge_tracks (event_id, near_address, track) values (?, ?, ?);",
+            &[
+                Value::Integer(event_id),
+                Value::String(near_address.clone()),
+                Value::String(track),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn get_judge_tracks(
+    conn: &Connection,
+    event_id: i64,
+    near_address: String,
+) -> Result<Vec<String>> {
+    db::query_all(
+        conn,
+        "select track from judge_tracks where event_id = ? AND near_address = ? order by track;",
+        &[Value::Integer(event_id), Value::String(near_address)],
+        |row| Ok(row[0].as_string().unwrap_or_default().to_string()),
+    )
+}
+
+// ASSIGNMENTS
+#[marine]
+#[derive(Default)]
+pub struct ReviewAssignment {
+    pub event_id: i64,
+    pub submission_id: i64,
+    pub judge: String,
+    pub project_name: String,
+    pub track: String,
+    pub reviewed: bool,
+    pub assigned_at: String,
+}
+
+impl ReviewAssignment {
+    pub fn from_row(row: &[Value]) -> Result<ReviewAssignment> {
+        Ok(ReviewAssignment {
+            event_id: row[0].as_integer().ok_or(get_none_error())?,
+            submission_id: row[1].as_integer().ok_or(get_none_error())?,
+            judge: row[2].as_string().ok_or(get_none_error())?.to_string(),
+            assigned_at: row[3].as_string().unwrap_or_default().to_string(),
+            project_name: row[4].as_string().unwrap_or_default().to_string(),
+            track: row[5].as_string().unwrap_or_default().to_string(),
+            reviewed: row[6].as_integer().unwrap_or_default() > 0,
+        })
+    }
+}
+
+#[marine]
+#[derive(Default)]
+pub struct AssignmentResult {
+    pub event_id: i64,
+    pub assignments: Vec<ReviewAssignment>,
+    pub unfilled: Vec<i64>,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl AssignmentResult {
+    pub fn from_res(
+        res: Result<(Vec<ReviewAssignment>, Vec<i64>)>,
+        event_id: i64,
+    ) -> AssignmentResult {
+        match res {
+            Ok((assignments, unfilled)) => AssignmentResult {
+                event_id,
+                assignments,
+                unfilled,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => AssignmentResult {
+                event_id,
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+const ASSIGNMENT_SELECT: &str = "
+    select a.event_id, a.submission_id, a.judge, a.assigned_at, s.project_name, s.track,
+      (select count(*) from reviews r where r.submission_id = a.submission_id AND r.judge = a.judge) as reviewed
+    from review_assignments a
+    join submissions s on s.uuid = a.submission_id
+";
+
+pub fn get_assignments(conn: &Connection, event_id: i64) -> Result<Vec<ReviewAssignment>> {
+    db::query_all(
+        conn,
+        &format!(
+            "{} where a.event_id = ? order by a.submission_id, a.judge;",
+            ASSIGNMENT_SELECT
+        ),
+        &[Value::Integer(event_id)],
+        ReviewAssignment::from_row,
+    )
+}
+
+/// Every assignment of `judge`, still to be reviewed first.
+pub fn get_review_queue(conn: &Connection, judge: String) -> Result<Vec<ReviewAssignment>> {
+    db::query_all(
+        conn,
+        &format!(
+            "{} where a.judge = ? order by reviewed, a.assigned_at, a.submission_id;",
+            ASSIGNMENT_SELECT
+        ),
+        &[Value::String(judge)],
+        ReviewAssignment::from_row,
+    )
+}
+
+/// Distributes the submitted projects of an event across its judges so
+/// each gets `reviews_per_submission` independent reviewers. Assignments
+/// that already have a review are kept, the rest are redrawn.
+///
+/// Projects with the fewest eligible judges are placed first. For each slot
+/// the judge is picked by, in order: being under the even share of the
+/// total load, expertise in the project's track, current load, address.
+/// Conflicted judges are never picked. Returns the assignments and the
+/// projects that could not get enough independent judges.
+pub fn assign_reviews(
+    conn: &Connection,
+    event_id: i64,
+    reviews_per_submission: i64,
+) -> Result<(Vec<ReviewAssignment>, Vec<i64>)> {
+    let event = db::get_event(conn, event_id)?;
+    if EventPhase::from_code(event.status)? != EventPhase::Judging {
+        return Err(Error::new(
+            ErrorCode::InvalidState,
+            "Reviews can only be assigned while the event is in judging",
+        ));
+    }
+    if reviews_per_submission < 1 {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "reviews_per_submission must be at least 1",
+        ));
+    }
+
+    let judges = get_event_judges(conn, event_id)?;
+    if judges.is_empty() {
+        return Err(Error::new(ErrorCode::InvalidState, "Event has no judges"));
+    }
+
+    db::execute(
+        conn,
+        "
+        delete from review_assignments
+        where event_id = ?
+        AND not exists (
+          select 1 from reviews r
+          where r.submission_id = review_assignments.submission_id AND r.judge = review_assignments.judge
+        );
+        ",
+        &[Value::Integer(event_id)],
+    )?;
+
+    let kept = get_assignments(conn, event_id)?;
+    let mut load: Vec<i64> = judges
+        .iter()
+        .map(|j| kept.iter().filter(|a| a.judge == j.near_address).count() as i64)
+        .collect();
+    let tracks = judges
+        .iter()
+        .map(|j| get_judge_tracks(conn, event_id, j.near_address.clone()))
+        .collect::<Result<Vec<Vec<String>>>>()?;
+
+    // per project: the judges it could still get, and how many it needs
+    let mut slots = Vec::new();
+    for submission in get_judging_submissions(conn, event_id)? {
+        let mut eligible = Vec::new();
+        for (i, judge) in judges.iter().enumerate() {
+            let assigned = kept
+                .iter()
+                .any(|a| a.submission_id == submission.uuid && a.judge == judge.near_address);
+            if !assigned
+                && conflicts::find_conflict(conn, &judge.near_address, &submission)?.is_none()
+            {
+                eligible.push(i);
+            }
+        }
+
+        let has = kept
+            .iter()
+            .filter(|a| a.submission_id == submission.uuid)
+            .count() as i64;
+        let needed = (reviews_per_submission - has).max(0);
+        slots.push((submission, eligible, needed));
+    }
+    slots.sort_by_key(|(submission, eligible, _)| (eligible.len(), submission.uuid));
+
+    let total: i64 =
+        load.iter().sum::<i64>() + slots.iter().map(|(_, _, needed)| needed).sum::<i64>();
+    let share = (total + judges.len() as i64 - 1) / judges.len() as i64;
+
+    let mut unfilled = Vec::new();
+    for (submission, mut eligible, needed) in slots {
+        for _ in 0..needed {
+            eligible.sort_by_key(|&i| {
+                let expert = !submission.track.is_empty() && tracks[i].contains(&submission.track);
+                (
+                    load[i] >= share,
+                    !expert,
+                    load[i],
+                    judges[i].near_address.clone(),
+                )
+            });
+
+            if eligible.is_empty() {
+                unfilled.push(submission.uuid);
+                break;
+            }
+
+            let i = eligible.remove(0);
+            db::execute(
+                conn,
+                "insert into review_assignments (event_id, submission_id, judge) values (?, ?, ?);",
+                &[
+                    Value::Integer(event_id),
+                    Value::Integer(submission.uuid),
+                    Value::String(judges[i].near_address.clone()),
+                ],
+            )?;
+            load[i] += 1;
+        }
+    }
+
+    Ok((get_assignments(conn, event_id)?, unfilled))
+}
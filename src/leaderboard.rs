@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::db::{self, get_none_error};
+use crate::error::Result;
+use crate::judging;
+use crate::status::{EventPhase, SubmissionStatus};
+
+#[marine]
+#[derive(Default, Clone)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub submission_id: i64,
+    pub project_name: String,
+    pub submit_by: String,
+    pub raw_score: f64,
+    pub normalized_score: f64,
+    pub review_count: i64,
+}
+
+impl LeaderboardEntry {
+    pub fn from_row(row: &[Value]) -> Result<LeaderboardEntry> {
+        Ok(LeaderboardEntry {
+            rank: row[1].as_integer().ok_or(get_none_error())?,
+            submission_id: row[2].as_integer().ok_or(get_none_error())?,
+            project_name: row[3].as_string().unwrap_or_default().to_string(),
+            submit_by: row[4].as_string().unwrap_or_default().to_string(),
+            raw_score: row[5].as_float().unwrap_or_default(),
+            normalized_score: row[6].as_float().unwrap_or_default(),
+            review_count: row[7].as_integer().unwrap_or_default(),
+        })
+    }
+}
+
+#[marine]
+#[derive(Default)]
+pub struct Leaderboard {
+    pub event_id: i64,
+    pub normalized: bool,
+    pub frozen: bool,
+    pub entries: Vec<LeaderboardEntry>,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl Leaderboard {
+    pub fn from_res(res: Result<Leaderboard>) -> Leaderboard {
+        match res {
+            Ok(v) => v,
+            Err(e) => Leaderboard {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+struct JudgeScore {
+    submission_id: i64,
+    judge: String,
+    total: f64,
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f64>() / values.len() as f64
+    }
+}
+
+/// Mean and population standard deviation of every total each judge gave
+/// in the event.
+fn judge_stats(scores: &[JudgeScore]) -> BTreeMap<&str, (f64, f64)> {
+    let mut totals: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for score in scores {
+        totals.entry(&score.judge).or_default().push(score.total);
+    }
+
+    totals
+        .into_iter()
+        .map(|(judge, values)| {
+            let m = mean(&values);
+            let variance = mean(&values.iter().map(|v| (v - m).powi(2)).collect::<Vec<f64>>());
+            (judge, (m, variance.sqrt()))
+        })
+        .collect()
+}
+
+/// Ranks the submitted projects of an event from its reviews. A project's
+/// raw score is the mean of its judges' weighted totals; its normalized
+/// score is the mean of those totals as z-scores against each judge's own
+/// mean and spread, so a harsh or lenient judge does not move the ranking.
+/// Ties fall back to the other score, then review count, then the earlier
+/// submission.
+pub fn compute(
+    conn: &Connection,
+    event_id: i64,
+    normalized: bool,
+) -> Result<Vec<LeaderboardEntry>> {
+    let submissions = judging::get_judging_submissions(conn, event_id)?;
+    let scores = db::query_all(
+        conn,
+        "
+        select r.submission_id, r.judge, r.total from reviews r
+        join submissions s on s.uuid = r.submission_id
+        where s.event_id = ? AND s.status = ?;
+        ",
+        &[
+            Value::Integer(event_id),
+            Value::Integer(SubmissionStatus::Submitted.code()),
+        ],
+        |row| {
+            Ok(JudgeScore {
+                submission_id: row[0].as_integer().ok_or(get_none_error())?,
+                judge: row[1].as_string().ok_or(get_none_error())?.to_string(),
+                total: row[2].as_float().unwrap_or_default(),
+            })
+        },
+    )?;
+    let stats = judge_stats(&scores);
+
+    let mut entries: Vec<LeaderboardEntry> = submissions
+        .into_iter()
+        .map(|submission| {
+            let own: Vec<&JudgeScore> = scores
+                .iter()
+                .filter(|s| s.submission_id == submission.uuid)
+                .collect();
+            let raw: Vec<f64> = own.iter().map(|s| s.total).collect();
+            let z: Vec<f64> = own
+                .iter()
+                .map(|s| match stats.get(s.judge.as_str()) {
+                    Some((m, sd)) if *sd > 0.0 => (s.total - m) / sd,
+                    _ => 0.0,
+                })
+                .collect();
+
+            LeaderboardEntry {
+                rank: 0,
+                submission_id: submission.uuid,
+                project_name: submission.project_name,
+                submit_by: submission.submit_by,
+                raw_score: mean(&raw),
+                normalized_score: mean(&z),
+                review_count: own.len() as i64,
+            }
+        })
+        .collect();
+
+    let key = |e: &LeaderboardEntry| {
+        if normalized {
+            (e.normalized_score, e.raw_score)
+        } else {
+            (e.raw_score, e.normalized_score)
+        }
+    };
+
+    // unreviewed projects go last, whatever their zero scores compare to
+    entries.sort_by(|a, b| {
+        (b.review_count > 0)
+            .cmp(&(a.review_count > 0))
+            .then(key(b).0.partial_cmp(&key(a).0).unwrap_or(Ordering::Equal))
+            .then(key(b).1.partial_cmp(&key(a).1).unwrap_or(Ordering::Equal))
+            .then(b.review_count.cmp(&a.review_count))
+            .then(a.submission_id.cmp(&b.submission_id))
+    });
+
+    for (i, entry) in entries.iter_mut().enumerate() {
+        entry.rank = i as i64 + 1;
+    }
+
+    Ok(entries)
+}
+
+/// Stores the current ranking as the event's published result. Called when
+/// the event moves to results_published; later reads return these rows.
+pub fn freeze(conn: &Connection, event_id: i64) -> Result<()> {
+    let event = db::get_event(conn, event_id)?;
+    let entries = compute(conn, event_id, event.normalize_scores)?;
+
+    db::execute(
+        conn,
+        "delete from leaderboard_entries where event_id = ?;",
+        &[Value::Integer(event_id)],
+    )?;
+
+    for entry in entries {
+        db::execute(
+            conn,
+            "
+            insert into leaderboard_entries (event_id, rank, submission_id, project_name, submit_by, raw_score, normalized_score, review_count)
+            values (?, ?, ?, ?, ?, ?, ?, ?);
+            ",
+            &[
+                Value::Integer(event_id),
+                Value::Integer(entry.rank),
+                Value::Integer(entry.submission_id),
+                Value::String(entry.project_name),
+                Value::String(entry.submit_by),
+                Value::Float(entry.raw_score),
+                Value::Float(entry.normalized_score),
+                Value::Integer(entry.review_count),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn is_published(phase: EventPhase) -> bool {
+    phase == EventPhase::ResultsPublished || phase == EventPhase::Archived
+}
+
+/// The frozen result once an event's results are published, otherwise the
+/// live ranking.
+pub fn get_event_leaderboard(conn: &Connection, event_id: i64) -> Result<Leaderboard> {
+    let event = db::get_event(conn, event_id)?;
+    let frozen = is_published(EventPhase::from_code(event.status)?);
+
+    let entries = if frozen {
+        db::query_all(
+            conn,
+            "select * from leaderboard_entries where event_id = ? order by rank;",
+            &[Value::Integer(event_id)],
+            LeaderboardEntry::from_row,
+        )?
+    } else {
+        compute(conn, event_id, event.normalize_scores)?
+    };
+
+    Ok(Leaderboard {
+        event_id,
+        normalized: event.normalize_scores,
+        frozen,
+        entries,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
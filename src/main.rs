@@ -1,11 +1,38 @@
 use marine_rs_sdk::{marine, module_manifest, WasmLoggerBuilder};
-use marine_sqlite_connector::{Error, Result};
 
 mod auth;
+mod backup;
+mod conflicts;
 mod db;
+mod error;
+mod judging;
+mod leaderboard;
+// `Store` kept in memory, for running the workflow under `cargo test`.
+#[cfg(test)]
+mod memory;
+mod migrations;
+mod near;
+mod page;
+mod reset;
+mod schedule;
+mod search;
+mod status;
+mod store;
+mod workflow;
 
 use auth::*;
+use backup::Backup;
+use conflicts::ConflictReport;
 use db::*;
+use error::Result;
+use judging::{AssignmentResult, Criterion, CriterionScore, EventJudge, Review, ReviewAssignment};
+use leaderboard::Leaderboard;
+use migrations::MigrationResult;
+use page::PageRequest;
+use reset::{ResetArchive, ResetResult};
+use search::{SubmissionSearchResult, UserSearchResult};
+use status::EventPhase;
+use store::{EventFields, SqliteStore, Store, SubmissionFields, UserProfile};
 
 module_manifest!();
 
@@ -14,12 +41,16 @@ pub fn main() {
         .with_log_level(log::LevelFilter::Info)
         .build()
         .unwrap();
+
+    let (path, source) = db::db_location();
+    log::info!("database file: {} (from {})", path, source);
 }
 
 #[marine]
 pub struct IFResult {
     pub success: bool,
     pub err_msg: String,
+    pub err_code: String,
 }
 
 impl IFResult {
@@ -28,48 +59,277 @@ impl IFResult {
             Ok(_v) => IFResult {
                 success: true,
                 err_msg: "".into(),
+                err_code: "".into(),
             },
             Err(e) => IFResult {
                 success: false,
                 err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
             },
         }
     }
+}
+
+#[marine]
+pub fn init_service() -> IFResult {
+    if !am_i_owner() {
+        return IFResult::from_res(Err(auth::get_not_owner_error()));
+    }
+
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let res = db::check_storage()
+        .and_then(|storage| {
+            log::info!(
+                "using database {} (configured {} from {})",
+                storage.opened_file,
+                storage.db_path,
+                storage.source
+            );
+            if !storage.persistent {
+                log::warn!("database is on volatile storage and will be lost on restart");
+            }
+            migrations::migrate(&conn)
+        })
+        .and_then(|_| auth::bootstrap_owner(&conn));
+    IFResult::from_res(res)
+}
+
+/// Which database file the service is using and whether it survives a
+/// restart.
+#[marine]
+pub fn get_storage_info() -> StorageInfo {
+    if !am_i_owner() {
+        return StorageInfo::from_res(Err(auth::get_not_owner_error()));
+    }
+
+    StorageInfo::from_res(db::check_storage())
+}
+
+#[marine]
+pub fn migrate_service() -> MigrationResult {
+    if !am_i_owner() {
+        return MigrationResult::from_res(Err(auth::get_not_owner_error()), 0);
+    }
+
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return MigrationResult::from_res(Err(e), 0),
+    };
+    let res = migrations::migrate(&conn);
+    let version = migrations::current_version(&conn).unwrap_or_default();
+
+    let mut result = MigrationResult::from_res(res, version);
+    if !result.success && version < migrations::UNIQUE_SUBMISSIONS_VERSION {
+        result.duplicate_submissions =
+            migrations::get_duplicate_submissions(&conn).unwrap_or_default();
+    }
+    result
+}
+
+/// Resetting takes two calls. With an empty `token` the current data is
+/// archived and a confirmation token returned; calling again with that
+/// token within ten minutes refreshes that archive and drops every table.
+#[marine]
+pub fn reset_service(token: String) -> ResetResult {
+    if !am_i_owner() {
+        return ResetResult::from_res(Err(auth::get_not_owner_error()));
+    }
 
-    pub fn from_err_str(e: &str) -> IFResult {
-        IFResult {
-            success: false,
-            err_msg: e.to_string(),
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return ResetResult::from_res(Err(e)),
+    };
+    let res = db::transaction(&conn, || {
+        if token.is_empty() {
+            reset::request_reset(&conn, get_init_peer_id())
+        } else {
+            reset::confirm_reset(&conn, token, get_init_peer_id())
         }
+    });
+
+    ResetResult::from_res(res)
+}
+
+#[marine]
+pub fn get_reset_archives() -> Vec<ResetArchive> {
+    if !am_i_owner() {
+        return Vec::new();
     }
+
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = reset::get_archives(&conn);
+    res.unwrap_or_default()
 }
 
 #[marine]
-pub fn init_service() -> IFResult {
+pub fn get_reset_archive(id: i64) -> Backup {
     if !am_i_owner() {
-        return IFResult::from_err_str("You are not the owner!");
+        return Backup::from_res(Err(auth::get_not_owner_error()));
     }
 
-    let conn = db::get_connection();
-    let res = db::create_tables(&conn);
-    IFResult::from_res(res)
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Backup::from_res(Err(e)),
+    };
+    Backup::from_res(reset::get_archive(&conn, id))
 }
 
 #[marine]
-pub fn reset_service() -> IFResult {
+pub fn backup_service() -> Backup {
     if !am_i_owner() {
-        return IFResult::from_err_str("You are not the owner!");
+        return Backup::from_res(Err(auth::get_not_owner_error()));
+    }
+
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Backup::from_res(Err(e)),
+    };
+    Backup::from_res(backup::backup(&conn))
+}
+
+#[marine]
+pub fn restore_service(dump: String) -> IFResult {
+    if !am_i_owner() {
+        return IFResult::from_res(Err(auth::get_not_owner_error()));
+    }
+
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    IFResult::from_res(backup::restore(&conn, &dump))
+}
+
+// roles
+#[marine]
+pub fn grant_role(peer_id: String, role: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let res = db::transaction(&conn, || {
+        Role::parse(&role).and_then(|role| auth::grant_role(&conn, peer_id, role))
+    });
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn revoke_role(peer_id: String, role: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let res = db::transaction(&conn, || {
+        Role::parse(&role).and_then(|role| auth::revoke_role(&conn, peer_id, role))
+    });
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn get_roles(peer_id: String) -> Vec<String> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = auth::get_roles(&conn, peer_id);
+    res.unwrap_or_default()
+        .iter()
+        .map(|r| r.as_str().to_string())
+        .collect()
+}
+
+// users
+#[marine]
+pub fn request_near_challenge(near_address: String) -> Challenge {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Challenge::from_res(Err(e)),
+    };
+    let res = auth::issue_challenge(&conn, near_address);
+    Challenge::from_res(res)
+}
+
+#[marine]
+pub fn verify_near_address(
+    near_address: String,
+    public_key: String,
+    signature: String,
+) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    // the challenge is consumed in its own transaction, so a failed answer
+    // cannot roll it back and be retried
+    let res = db::transaction(&conn, || auth::take_challenge(&conn, near_address.clone()))
+        .and_then(|nonce| {
+            db::transaction(&conn, || {
+                auth::verify_near_address(&conn, near_address, public_key, signature, &nonce)
+            })
+        });
+    IFResult::from_res(res)
+}
+
+/// Organizers attest the access keys of named NEAR accounts, which cannot be
+/// looked up from inside the module, before their owners can verify.
+#[marine]
+pub fn add_near_access_key(near_address: String, public_key: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
+    }
+
+    let res = auth::attest_access_key(&conn, near_address, public_key);
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn remove_near_access_key(near_address: String, public_key: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
     }
 
-    let conn = db::get_connection();
-    let res = db::delete_tables(&conn);
+    let res = db::transaction(&conn, || {
+        auth::revoke_access_key(&conn, near_address, public_key)
+    });
     IFResult::from_res(res)
 }
 
+#[marine]
+pub fn get_near_access_keys(near_address: String) -> Vec<String> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = auth::get_access_keys(&conn, near_address);
+    res.unwrap_or_default()
+}
+
 #[marine]
 pub fn register_user(near_address: String, email: String) -> User {
-    let conn = db::get_connection();
-    let res = db::add_user(&conn, near_address, email);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return User::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_near_address(&conn, near_address.clone()) {
+        return User::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || store.add_user(near_address, email));
     User::from_res(res)
 }
 
@@ -84,10 +344,16 @@ pub fn update_user(
     linkedin: String,
     twitter: String,
 ) -> User {
-    let conn = db::get_connection();
-    let res = db::update_user(
-        &conn,
-        near_address,
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return User::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_near_address(&conn, near_address.clone()) {
+        return User::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let profile = UserProfile {
         first_name,
         last_name,
         is_student,
@@ -95,18 +361,94 @@ pub fn update_user(
         git,
         linkedin,
         twitter,
-    );
+    };
+    let res = db::transaction(&conn, || store.update_user(near_address, profile));
     User::from_res(res)
 }
 
+#[marine]
+pub fn get_my_user() -> User {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return User::from_res(Err(e)),
+    };
+    let user = auth::get_caller_address(&conn)
+        .and_then(|address| SqliteStore::new(&conn).get_user(address));
+
+    User::from_res(user)
+}
+
 #[marine]
 pub fn get_user(near_address: String) -> User {
-    let conn = db::get_connection();
-    let user = db::get_user(&conn, near_address);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return User::from_res(Err(e)),
+    };
+    let user = SqliteStore::new(&conn).get_user(near_address);
 
     User::from_res(user)
 }
 
+/// Declares the organizations the caller is affiliated with, replacing any
+/// earlier declaration. Used to detect judge conflicts of interest.
+#[marine]
+pub fn set_my_organizations(organizations: Vec<String>) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let res = db::transaction(&conn, || {
+        auth::get_caller_address(&conn).and_then(|caller| {
+            SqliteStore::new(&conn).get_user(caller.clone())?;
+            conflicts::set_organizations(&conn, caller, organizations)
+        })
+    });
+
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn get_user_organizations(near_address: String) -> Vec<String> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = conflicts::get_organizations(&conn, near_address);
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn list_users(filter: UserFilter, page: PageRequest) -> UserPage {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return UserPage::from_res(Err(e)),
+    };
+
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return UserPage::from_res(Err(e));
+    }
+
+    let res = db::list_users(&conn, filter, page);
+    UserPage::from_res(res)
+}
+
+#[marine]
+pub fn search_users(query: String) -> UserSearchResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return UserSearchResult::from_res(Err(e)),
+    };
+
+    // hits carry full profiles, emails included, like list_users
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return UserSearchResult::from_res(Err(e));
+    }
+
+    let res = search::search_users(&conn, &query);
+
+    UserSearchResult::from_res(res)
+}
+
 #[marine]
 pub fn draft(
     event_id: i64,
@@ -116,51 +458,30 @@ pub fn draft(
     git: String,
     live_url: String,
     video_url: String,
-    submit_by: String,
 ) -> Submission {
-    let conn = db::get_connection();
-
-    // check user
-    match db::get_user(&conn, submit_by.clone()) {
-        Ok(_) => {
-            match db::get_event(&conn, event_id) {
-                Ok(_) => {
-                    // check if user already submitted
-                    let user_submission =
-                        db::get_user_submission_for_event(&conn, submit_by.clone(), event_id);
-
-                    if !user_submission.unwrap().success {
-                        let submission = db::add_submission(
-                            &conn,
-                            event_id,
-                            name,
-                            description,
-                            thumbnail,
-                            git,
-                            live_url,
-                            video_url,
-                            submit_by,
-                        );
-
-                        Submission::from_res(submission)
-                    } else {
-                        Submission::from_res(Err(Error {
-                            code: None,
-                            message: Some("User have submitted project".to_string()),
-                        }))
-                    }
-                }
-                Err(e) => Submission::from_res(Err(Error {
-                    code: None,
-                    message: Some(e.to_string().to_string()),
-                })),
-            }
-        }
-        Err(err) => Submission::from_res(Err(Error {
-            code: None,
-            message: Some(err.to_string().to_string()),
-        })),
-    }
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let fields = SubmissionFields {
+        name,
+        description,
+        thumbnail,
+        git,
+        live_url,
+        video_url,
+    };
+    let res = db::transaction(&conn, || {
+        auth::get_caller_address(&conn).and_then(|submit_by| {
+            let submission = workflow::create_draft(&store, submit_by, event_id, fields)?;
+            db::add_submission_revision(&conn, &submission, get_init_peer_id())?;
+
+            Ok(submission)
+        })
+    });
+
+    Submission::from_res(res)
 }
 
 #[marine]
@@ -172,63 +493,318 @@ pub fn update_submission(
     git: String,
     live_url: String,
     video_url: String,
-) {
-    let conn = db::get_connection();
-    let submission = db::get_submission(&conn, id).expect("No submission record");
-
-    if submission.status == 1 {
-        db::update_submission(
-            &conn,
-            id,
-            name,
-            description,
-            thumbnail,
-            git,
-            live_url,
-            video_url,
-        )
-        .unwrap_or_default();
-    }
+) -> Submission {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let fields = SubmissionFields {
+        name,
+        description,
+        thumbnail,
+        git,
+        live_url,
+        video_url,
+    };
+    let res = db::transaction(&conn, || {
+        store.get_submission(id).and_then(|submission| {
+            auth::require_submission_member(&conn, &submission)?;
+
+            let submission = workflow::edit_draft(&store, &submission, fields)?;
+            db::add_submission_revision(&conn, &submission, get_init_peer_id())?;
+
+            Ok(submission)
+        })
+    });
+
+    Submission::from_res(res)
 }
 
 #[marine]
 pub fn submit(id: i64) -> Submission {
-    let conn = db::get_connection();
-    let submission = db::submit_submission(&conn, id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        let submission = store.get_submission(id)?;
+        auth::require_submission_member(&conn, &submission)?;
 
-    Submission::from_res(submission)
+        let submission = workflow::submit(&store, &submission)?;
+        db::revoke_invitations(&conn, id)?;
+        Ok(submission)
+    });
+
+    Submission::from_res(res)
+}
+
+#[marine]
+pub fn withdraw_submission(id: i64) -> Submission {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        let submission = store.get_submission(id)?;
+        auth::require_submission_member(&conn, &submission)?;
+
+        let submission = workflow::withdraw(&store, &submission)?;
+        db::revoke_invitations(&conn, id)?;
+        Ok(submission)
+    });
+
+    Submission::from_res(res)
+}
+
+#[marine]
+pub fn reopen_submission(id: i64) -> Submission {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        store.get_submission(id).and_then(|submission| {
+            auth::require_role(&conn, Role::Organizer)?;
+            workflow::reopen(&store, &submission)
+        })
+    });
+
+    Submission::from_res(res)
+}
+
+/// Submission members can change the track of a draft, organizers at any
+/// time.
+#[marine]
+pub fn set_submission_track(id: i64, track: String) -> Submission {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        store.get_submission(id).and_then(|submission| {
+            if !auth::is_organizer(&conn)? {
+                auth::require_submission_member(&conn, &submission)?;
+                status::require_draft(&submission)?;
+            }
+
+            store.set_submission_track(id, track)
+        })
+    });
+
+    Submission::from_res(res)
 }
 
 #[marine]
 pub fn get_submission(id: i64) -> Submission {
-    let conn = db::get_connection();
-    let submission = db::get_submission(&conn, id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let submission = SqliteStore::new(&conn).get_submission(id);
 
     Submission::from_res(submission)
 }
 
+#[marine]
+pub fn get_submission_history(id: i64) -> Vec<SubmissionRevision> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = db::get_history(&conn, id);
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn get_submission_revision(id: i64, rev: i64) -> SubmissionRevision {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return SubmissionRevision::from_res(Err(e)),
+    };
+    let res = db::get_revision(&conn, id, rev);
+    SubmissionRevision::from_res(res)
+}
+
 #[marine]
 pub fn get_user_event_submission(address: String, event_id: i64) -> Submission {
-    let conn = db::get_connection();
-    let submission = db::get_user_submission_for_event(&conn, address, event_id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Submission::from_res(Err(e)),
+    };
+    let submission = SqliteStore::new(&conn).get_user_submission_for_event(address, event_id);
 
     Submission::from_res(submission)
 }
 
 #[marine]
 pub fn get_submissions() -> Vec<Submission> {
-    let conn = db::get_connection();
-    let res = db::get_submissions(&conn);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = SqliteStore::new(&conn).get_submissions();
     res.unwrap_or_default()
 }
 
 #[marine]
 pub fn get_event_submissions(event_id: i64) -> Vec<Submission> {
-    let conn = db::get_connection();
-    let res = db::get_submissions_by_event(&conn, event_id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = SqliteStore::new(&conn).get_submissions_by_event(event_id);
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn list_submissions(filter: SubmissionFilter, page: PageRequest) -> SubmissionPage {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return SubmissionPage::from_res(Err(e)),
+    };
+    let res = db::list_submissions(&conn, filter, page);
+
+    SubmissionPage::from_res(res)
+}
+
+#[marine]
+pub fn search_submissions(query: String, event_id: i64, page: i64) -> SubmissionSearchResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return SubmissionSearchResult::from_res(Err(e), page),
+    };
+    let res = search::search_submissions(&conn, &query, event_id, page);
+
+    SubmissionSearchResult::from_res(res, page)
+}
+
+// team
+/// Adds a member without asking them, which also keeps them from drafting
+/// their own project for the event, so only organizers may. Owners grow
+/// their team with `invite_team_member` and `accept_invitation`.
+#[marine]
+pub fn add_team_member(submission_id: i64, near_address: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        store.get_submission(submission_id).and_then(|submission| {
+            auth::require_role(&conn, Role::Organizer)?;
+            status::require_draft(&submission)?;
+            status::require_drafts_open(&store.get_event(submission.event_id)?)?;
+
+            store.add_team_member(submission_id, near_address)
+        })
+    });
+
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn remove_team_member(submission_id: i64, near_address: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        store.get_submission(submission_id).and_then(|submission| {
+            // members may leave on their own, everyone else needs the owner
+            let caller = auth::get_caller_address(&conn)?;
+            if caller != near_address {
+                auth::require_submission_owner(&conn, &submission)?;
+            }
+            status::require_draft(&submission)?;
+            status::require_drafts_open(&store.get_event(submission.event_id)?)?;
+
+            store.remove_team_member(submission_id, near_address)
+        })
+    });
+
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn get_submission_team(submission_id: i64) -> Vec<TeamMember> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = SqliteStore::new(&conn).get_submission_team(submission_id);
     res.unwrap_or_default()
 }
 
+#[marine]
+pub fn invite_team_member(submission_id: i64, invitee: String) -> Invitation {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Invitation::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        store.get_submission(submission_id).and_then(|submission| {
+            auth::require_submission_owner(&conn, &submission)?;
+            status::require_draft(&submission)?;
+            status::require_drafts_open(&store.get_event(submission.event_id)?)?;
+
+            db::add_invitation(&conn, submission_id, invitee, submission.submit_by)
+        })
+    });
+
+    Invitation::from_res(res)
+}
+
+#[marine]
+pub fn get_my_invitations() -> Vec<Invitation> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = auth::get_caller_address(&conn)
+        .and_then(|address| db::get_pending_invitations(&conn, address));
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn accept_invitation(id: i64) -> Invitation {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Invitation::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        auth::get_caller_address(&conn).and_then(|address| {
+            let invitation = db::get_invitation(&conn, id)?;
+            let submission = store.get_submission(invitation.submission_id)?;
+            status::require_draft(&submission)?;
+            status::require_drafts_open(&store.get_event(submission.event_id)?)?;
+
+            db::answer_invitation(&conn, id, address, INVITATION_ACCEPTED)
+        })
+    });
+    Invitation::from_res(res)
+}
+
+#[marine]
+pub fn decline_invitation(id: i64) -> Invitation {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Invitation::from_res(Err(e)),
+    };
+    let res = db::transaction(&conn, || {
+        auth::get_caller_address(&conn)
+            .and_then(|address| db::answer_invitation(&conn, id, address, INVITATION_DECLINED))
+    });
+    Invitation::from_res(res)
+}
+
 // event
 #[marine]
 pub fn add_event(
@@ -238,8 +814,23 @@ pub fn add_event(
     end_date: String,
     logo: String,
 ) -> Event {
-    let conn = db::get_connection();
-    let res = db::add_event(&conn, title, event_type, start_date, end_date, logo);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let fields = EventFields {
+        title,
+        event_type,
+        start_date,
+        end_date,
+        logo,
+    };
+    let res = db::transaction(&conn, || store.add_event(fields));
     Event::from_res(res)
 }
 
@@ -252,36 +843,383 @@ pub fn update_event(
     end_date: String,
     logo: String,
 ) -> Event {
-    let conn = db::get_connection();
-    let res = db::update_event(&conn, id, title, event_type, start_date, end_date, logo);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let fields = EventFields {
+        title,
+        event_type,
+        start_date,
+        end_date,
+        logo,
+    };
+    let res = db::transaction(&conn, || store.update_event(id, fields));
     Event::from_res(res)
 }
 
+/// Closing an event stops submissions and starts judging.
 #[marine]
 pub fn close_event(id: i64) -> Event {
-    let conn = db::get_connection();
-    let res = db::close_event(&conn, id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || store.transition_event(id, EventPhase::Judging));
+    Event::from_res(res)
+}
+
+#[marine]
+pub fn transition_event(id: i64, phase: String) -> Event {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || {
+        EventPhase::parse(&phase).and_then(|phase| store.transition_event(id, phase))
+    });
+    Event::from_res(res)
+}
+
+#[marine]
+pub fn set_event_team_size(id: i64, max_team_size: i64) -> Event {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || store.set_event_team_size(id, max_team_size));
+    Event::from_res(res)
+}
+
+#[marine]
+pub fn set_event_grace_period(id: i64, minutes: i64) -> Event {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || store.set_event_grace_period(id, minutes));
     Event::from_res(res)
 }
 
 #[marine]
 pub fn get_event(id: i64) -> Event {
-    let conn = db::get_connection();
-    let res = db::get_event(&conn, id);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    let res = SqliteStore::new(&conn).get_event(id);
 
     Event::from_res(res)
 }
 
 #[marine]
 pub fn get_events() -> Vec<Event> {
-    let conn = db::get_connection();
-    let res = db::get_events(&conn);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = SqliteStore::new(&conn).get_events();
     res.unwrap_or_default()
 }
 
 #[marine]
 pub fn get_live_events() -> Vec<Event> {
-    let conn = db::get_connection();
-    let res = db::get_live_events(&conn);
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = SqliteStore::new(&conn).get_live_events();
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn list_events(filter: EventFilter, page: PageRequest) -> EventPage {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return EventPage::from_res(Err(e)),
+    };
+    let res = db::list_events(&conn, filter, page);
+
+    EventPage::from_res(res)
+}
+
+// judging
+#[marine]
+pub fn add_event_judge(event_id: i64, near_address: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
+    }
+
+    let res = db::transaction(&conn, || {
+        auth::require_judge(&conn, near_address.clone())?;
+        judging::add_event_judge(&conn, event_id, near_address, auth::get_init_peer_id())
+    });
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn remove_event_judge(event_id: i64, near_address: String) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
+    }
+
+    let res = db::transaction(&conn, || {
+        judging::remove_event_judge(&conn, event_id, near_address)
+    });
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn get_event_judges(event_id: i64) -> Vec<EventJudge> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = judging::get_event_judges(&conn, event_id);
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn get_judge_tracks(event_id: i64, near_address: String) -> Vec<String> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = judging::get_judge_tracks(&conn, event_id, near_address);
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn add_rubric_criterion(
+    event_id: i64,
+    name: String,
+    description: String,
+    weight: f64,
+    max_score: i64,
+) -> Criterion {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Criterion::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Criterion::from_res(Err(e));
+    }
+
+    let res = db::transaction(&conn, || {
+        judging::add_criterion(&conn, event_id, name, description, weight, max_score)
+    });
+    Criterion::from_res(res)
+}
+
+#[marine]
+pub fn remove_rubric_criterion(id: i64) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
+    }
+
+    let res = db::transaction(&conn, || judging::remove_criterion(&conn, id));
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn get_rubric(event_id: i64) -> Vec<Criterion> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = judging::get_rubric(&conn, event_id);
     res.unwrap_or_default()
 }
+
+#[marine]
+pub fn set_judge_tracks(event_id: i64, near_address: String, tracks: Vec<String>) -> IFResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return IFResult::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return IFResult::from_res(Err(e));
+    }
+
+    let res = db::transaction(&conn, || {
+        judging::set_judge_tracks(&conn, event_id, near_address, tracks)
+    });
+    IFResult::from_res(res)
+}
+
+#[marine]
+pub fn assign_reviews(event_id: i64, reviews_per_submission: i64) -> AssignmentResult {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return AssignmentResult::from_res(Err(e), event_id),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return AssignmentResult::from_res(Err(e), event_id);
+    }
+
+    let res = db::transaction(&conn, || {
+        judging::assign_reviews(&conn, event_id, reviews_per_submission)
+    });
+    AssignmentResult::from_res(res, event_id)
+}
+
+#[marine]
+pub fn get_my_review_queue() -> Vec<ReviewAssignment> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res =
+        auth::get_caller_address(&conn).and_then(|judge| judging::get_review_queue(&conn, judge));
+    res.unwrap_or_default()
+}
+
+/// Submitted projects of an event, for its judges and organizers.
+#[marine]
+pub fn get_judging_submissions(event_id: i64) -> Vec<Submission> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let res = auth::is_organizer(&conn).and_then(|organizer| {
+        if !organizer {
+            auth::require_event_judge(&conn, event_id)?;
+        }
+        judging::get_judging_submissions(&conn, event_id)
+    });
+    res.unwrap_or_default()
+}
+
+#[marine]
+pub fn score_submission(
+    submission_id: i64,
+    criterion_scores: Vec<CriterionScore>,
+    comment: String,
+) -> Review {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Review::from_res(Err(e)),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = store.get_submission(submission_id).and_then(|submission| {
+        let judge = auth::require_event_judge(&conn, submission.event_id)?;
+        // checked before the transaction, so a refusal still records the
+        // conflict
+        conflicts::require_independent(&conn, &judge, &submission)?;
+
+        db::transaction(&conn, || {
+            judging::score_submission(&conn, submission_id, judge, criterion_scores, comment)
+        })
+    });
+
+    Review::from_res(res)
+}
+
+/// Organizers see every review of a submission, judges of its event only
+/// their own.
+#[marine]
+pub fn get_scores(submission_id: i64) -> Vec<Review> {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(_) => return Vec::new(),
+    };
+    let store = SqliteStore::new(&conn);
+    let res = store.get_submission(submission_id).and_then(|submission| {
+        if auth::is_organizer(&conn)? {
+            return judging::get_reviews(&conn, submission_id);
+        }
+
+        let judge = auth::require_event_judge(&conn, submission.event_id)?;
+        Ok(judging::get_reviews(&conn, submission_id)?
+            .into_iter()
+            .filter(|review| review.judge == judge)
+            .collect())
+    });
+    res.unwrap_or_default()
+}
+
+/// Published results are public; the live ranking during judging is for
+/// organizers only.
+#[marine]
+pub fn get_event_leaderboard(event_id: i64) -> Leaderboard {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Leaderboard::from_res(Err(e)),
+    };
+    let res = leaderboard::get_event_leaderboard(&conn, event_id).and_then(|board| {
+        if !board.frozen {
+            auth::require_role(&conn, Role::Organizer)?;
+        }
+        Ok(board)
+    });
+
+    Leaderboard::from_res(res)
+}
+
+#[marine]
+pub fn set_event_score_normalization(id: i64, enabled: bool) -> Event {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return Event::from_res(Err(e)),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return Event::from_res(Err(e));
+    }
+
+    let store = SqliteStore::new(&conn);
+    let res = db::transaction(&conn, || store.set_event_score_normalization(id, enabled));
+    Event::from_res(res)
+}
+
+/// Not a pure read: conflicts are re-detected and the recorded ones updated
+/// before the report is built, so this runs in a write transaction.
+#[marine]
+pub fn get_judge_conflicts(event_id: i64) -> ConflictReport {
+    let conn = match db::get_connection() {
+        Ok(conn) => conn,
+        Err(e) => return ConflictReport::from_res(Err(e), event_id),
+    };
+    if let Err(e) = auth::require_role(&conn, Role::Organizer) {
+        return ConflictReport::from_res(Err(e), event_id);
+    }
+
+    let res = db::transaction(&conn, || conflicts::get_judge_conflicts(&conn, event_id));
+    ConflictReport::from_res(res, event_id)
+}
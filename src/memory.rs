@@ -0,0 +1,441 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use crate::db::{self, Event, Submission, TeamMember, User};
+use crate::error::{Error, ErrorCode, Result};
+use crate::schedule;
+use crate::status::{self, EventPhase, SubmissionStatus};
+use crate::store::{EventFields, Store, SubmissionFields, UserProfile};
+
+/// `Store` kept in memory, mirroring the constraints the SQLite schema
+/// enforces. The search index and leaderboards live outside the store, so
+/// nothing is indexed and publishing results freezes nothing.
+#[derive(Default)]
+pub struct MemoryStore {
+    users: RefCell<BTreeMap<String, User>>,
+    events: RefCell<BTreeMap<i64, Event>>,
+    submissions: RefCell<BTreeMap<i64, Submission>>,
+    teams: RefCell<BTreeMap<i64, Vec<String>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        Default::default()
+    }
+
+    fn next_id<T>(rows: &BTreeMap<i64, T>) -> i64 {
+        rows.keys().next_back().copied().unwrap_or_default() + 1
+    }
+}
+
+impl Store for MemoryStore {
+    fn add_user(&self, near_address: String, email: String) -> Result<User> {
+        let mut users = self.users.borrow_mut();
+
+        if users.contains_key(&near_address) || users.values().any(|u| u.email == email) {
+            return Err(Error::new(ErrorCode::Conflict, "User already exists"));
+        }
+
+        let user = User {
+            near_address: near_address.clone(),
+            email,
+            success: true,
+            ..Default::default()
+        };
+        users.insert(near_address, user.clone());
+
+        Ok(user)
+    }
+
+    fn get_user(&self, near_address: String) -> Result<User> {
+        self.users
+            .borrow()
+            .get(&near_address)
+            .cloned()
+            .ok_or_else(db::get_none_error)
+    }
+
+    fn update_user(&self, near_address: String, profile: UserProfile) -> Result<User> {
+        let mut users = self.users.borrow_mut();
+        let user = users
+            .get_mut(&near_address)
+            .ok_or_else(db::get_none_error)?;
+
+        user.first_name = profile.first_name;
+        user.last_name = profile.last_name;
+        user.is_student = profile.is_student as i64;
+        user.country = profile.country;
+        user.git_handler = profile.git;
+        user.linkedin_handler = profile.linkedin;
+        user.twitter_handler = profile.twitter;
+
+        Ok(user.clone())
+    }
+
+    fn add_event(&self, fields: EventFields) -> Result<Event> {
+        schedule::validate_event_dates(&fields.start_date, &fields.end_date)?;
+
+        let mut events = self.events.borrow_mut();
+        let event = Event {
+            id: MemoryStore::next_id(&events),
+            title: fields.title,
+            event_type: fields.event_type,
+            start_date: fields.start_date,
+            end_date: fields.end_date,
+            logo: fields.logo,
+            status: EventPhase::Draft.code(),
+            max_team_size: 5,
+            grace_period_minutes: 0,
+            normalize_scores: true,
+            success: true,
+            ..Default::default()
+        };
+        events.insert(event.id, event.clone());
+
+        Ok(event)
+    }
+
+    fn update_event(&self, id: i64, fields: EventFields) -> Result<Event> {
+        schedule::validate_event_dates(&fields.start_date, &fields.end_date)?;
+
+        let mut events = self.events.borrow_mut();
+        let event = events.get_mut(&id).ok_or_else(db::get_none_error)?;
+
+        event.title = fields.title;
+        event.event_type = fields.event_type;
+        event.start_date = fields.start_date;
+        event.end_date = fields.end_date;
+        event.logo = fields.logo;
+
+        Ok(event.clone())
+    }
+
+    fn get_event(&self, id: i64) -> Result<Event> {
+        self.events
+            .borrow()
+            .get(&id)
+            .cloned()
+            .ok_or_else(db::get_none_error)
+    }
+
+    fn get_events(&self) -> Result<Vec<Event>> {
+        Ok(self.events.borrow().values().cloned().collect())
+    }
+
+    fn get_live_events(&self) -> Result<Vec<Event>> {
+        let live = EventPhase::RegistrationOpen.code()..=EventPhase::ResultsPublished.code();
+
+        Ok(self
+            .events
+            .borrow()
+            .values()
+            .filter(|e| live.contains(&e.status))
+            .cloned()
+            .collect())
+    }
+
+    fn transition_event(&self, id: i64, phase: EventPhase) -> Result<Event> {
+        let mut events = self.events.borrow_mut();
+        let event = events.get_mut(&id).ok_or_else(db::get_none_error)?;
+
+        status::require_event_transition(event, phase)?;
+        event.status = phase.code();
+
+        Ok(event.clone())
+    }
+
+    fn set_event_team_size(&self, id: i64, max_team_size: i64) -> Result<Event> {
+        db::validate_team_size(max_team_size)?;
+
+        let mut events = self.events.borrow_mut();
+        let event = events.get_mut(&id).ok_or_else(db::get_none_error)?;
+        event.max_team_size = max_team_size;
+
+        Ok(event.clone())
+    }
+
+    fn set_event_grace_period(&self, id: i64, minutes: i64) -> Result<Event> {
+        db::validate_grace_period(minutes)?;
+
+        let mut events = self.events.borrow_mut();
+        let event = events.get_mut(&id).ok_or_else(db::get_none_error)?;
+        event.grace_period_minutes = minutes;
+
+        Ok(event.clone())
+    }
+
+    fn set_event_score_normalization(&self, id: i64, enabled: bool) -> Result<Event> {
+        let mut events = self.events.borrow_mut();
+        let event = events.get_mut(&id).ok_or_else(db::get_none_error)?;
+
+        db::require_unpublished(event)?;
+        event.normalize_scores = enabled;
+
+        Ok(event.clone())
+    }
+
+    fn add_submission(
+        &self,
+        event_id: i64,
+        submit_by: String,
+        fields: SubmissionFields,
+    ) -> Result<Submission> {
+        if self.has_submission_for_event(submit_by.clone(), event_id)? {
+            return Err(db::get_duplicate_submission_error());
+        }
+
+        let mut submissions = self.submissions.borrow_mut();
+
+        let submission = Submission {
+            uuid: MemoryStore::next_id(&submissions),
+            event_id,
+            project_name: fields.name,
+            description: fields.description,
+            thumbnail: fields.thumbnail,
+            git_url: fields.git,
+            live_demo_url: fields.live_url,
+            video_demo_url: fields.video_url,
+            submit_by,
+            status: SubmissionStatus::Draft.code(),
+            status_name: SubmissionStatus::Draft.as_str().to_string(),
+            success: true,
+            ..Default::default()
+        };
+        submissions.insert(submission.uuid, submission.clone());
+
+        Ok(submission)
+    }
+
+    fn update_submission(&self, id: i64, fields: SubmissionFields) -> Result<Submission> {
+        let mut submissions = self.submissions.borrow_mut();
+        let submission = submissions.get_mut(&id).ok_or_else(db::get_none_error)?;
+
+        submission.project_name = fields.name;
+        submission.description = fields.description;
+        submission.thumbnail = fields.thumbnail;
+        submission.git_url = fields.git;
+        submission.live_demo_url = fields.live_url;
+        submission.video_demo_url = fields.video_url;
+
+        Ok(submission.clone())
+    }
+
+    fn get_submission(&self, id: i64) -> Result<Submission> {
+        self.submissions
+            .borrow()
+            .get(&id)
+            .cloned()
+            .ok_or_else(db::get_none_error)
+    }
+
+    fn get_submissions(&self) -> Result<Vec<Submission>> {
+        Ok(self.submissions.borrow().values().cloned().collect())
+    }
+
+    fn get_submissions_by_event(&self, event_id: i64) -> Result<Vec<Submission>> {
+        Ok(self
+            .submissions
+            .borrow()
+            .values()
+            .filter(|s| s.event_id == event_id)
+            .cloned()
+            .collect())
+    }
+
+    fn get_user_submission_for_event(&self, address: String, event_id: i64) -> Result<Submission> {
+        let teams = self.teams.borrow();
+        let in_team = |id: &i64| teams.get(id).is_some_and(|team| team.contains(&address));
+
+        self.submissions
+            .borrow()
+            .values()
+            .find(|s| s.event_id == event_id && (s.submit_by == address || in_team(&s.uuid)))
+            .cloned()
+            .ok_or_else(db::get_none_error)
+    }
+
+    fn has_submission_for_event(&self, address: String, event_id: i64) -> Result<bool> {
+        Ok(self
+            .get_user_submission_for_event(address, event_id)
+            .is_ok())
+    }
+
+    fn transition_submission(&self, id: i64, status: SubmissionStatus) -> Result<Submission> {
+        let mut submissions = self.submissions.borrow_mut();
+        let submission = submissions.get_mut(&id).ok_or_else(db::get_none_error)?;
+
+        status::require_submission_transition(submission, status)?;
+        submission.status = status.code();
+        submission.status_name = status.as_str().to_string();
+
+        Ok(submission.clone())
+    }
+
+    fn set_submission_track(&self, id: i64, track: String) -> Result<Submission> {
+        let mut submissions = self.submissions.borrow_mut();
+        let submission = submissions.get_mut(&id).ok_or_else(db::get_none_error)?;
+        submission.track = db::normalize_track(&track);
+
+        Ok(submission.clone())
+    }
+
+    fn get_submission_team(&self, submission_id: i64) -> Result<Vec<TeamMember>> {
+        let teams = self.teams.borrow();
+        let team = teams
+            .get(&submission_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        Ok(team
+            .iter()
+            .map(|near_address| TeamMember {
+                submission_id,
+                near_address: near_address.clone(),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    fn add_team_member(&self, submission_id: i64, near_address: String) -> Result<()> {
+        let submission = self.get_submission(submission_id)?;
+        let event = self.get_event(submission.event_id)?;
+        self.get_user(near_address.clone())?;
+
+        if self.has_submission_for_event(near_address.clone(), submission.event_id)? {
+            return Err(db::get_duplicate_submission_error());
+        }
+
+        let mut teams = self.teams.borrow_mut();
+        let team = teams.entry(submission_id).or_default();
+        if team.len() as i64 + 1 >= event.max_team_size {
+            return Err(Error::new(
+                ErrorCode::Conflict,
+                format!(
+                    "Team is full, this event allows {} members",
+                    event.max_team_size
+                ),
+            ));
+        }
+        team.push(near_address);
+
+        Ok(())
+    }
+
+    fn remove_team_member(&self, submission_id: i64, near_address: String) -> Result<()> {
+        let mut teams = self.teams.borrow_mut();
+        let team = teams
+            .get_mut(&submission_id)
+            .filter(|team| team.contains(&near_address))
+            .ok_or_else(db::get_none_error)?;
+        team.retain(|member| member != &near_address);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+
+    fn fields(name: &str) -> SubmissionFields {
+        SubmissionFields {
+            name: name.to_string(),
+            description: "description".to_string(),
+            thumbnail: "".to_string(),
+            git: "https://github.com/academy/project".to_string(),
+            live_url: "".to_string(),
+            video_url: "https://youtu.be/demo".to_string(),
+        }
+    }
+
+    /// Store with alice's submission to an event bob can join.
+    fn store_with_submission() -> (MemoryStore, Submission) {
+        let store = MemoryStore::new();
+        store
+            .add_user(ALICE.to_string(), "alice@example.com".to_string())
+            .unwrap();
+        store
+            .add_user(BOB.to_string(), "bob@example.com".to_string())
+            .unwrap();
+
+        let event = store
+            .add_event(EventFields {
+                title: "Hackathon".to_string(),
+                event_type: "hackathon".to_string(),
+                start_date: "2020-01-01T00:00:00Z".to_string(),
+                end_date: "".to_string(),
+                logo: "".to_string(),
+            })
+            .unwrap();
+        let submission = store
+            .add_submission(event.id, ALICE.to_string(), fields("Rocket"))
+            .unwrap();
+
+        (store, submission)
+    }
+
+    #[test]
+    fn team_members_find_the_submission_they_joined() {
+        let (store, submission) = store_with_submission();
+        store
+            .add_team_member(submission.uuid, BOB.to_string())
+            .unwrap();
+
+        let found = store
+            .get_user_submission_for_event(BOB.to_string(), submission.event_id)
+            .unwrap();
+
+        assert_eq!(found.uuid, submission.uuid);
+        assert_eq!(store.get_submission_team(submission.uuid).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn removed_members_are_free_to_draft_again() {
+        let (store, submission) = store_with_submission();
+        store
+            .add_team_member(submission.uuid, BOB.to_string())
+            .unwrap();
+
+        store
+            .remove_team_member(submission.uuid, BOB.to_string())
+            .unwrap();
+
+        assert!(!store
+            .has_submission_for_event(BOB.to_string(), submission.event_id)
+            .unwrap());
+        let again = store.remove_team_member(submission.uuid, BOB.to_string());
+        assert_eq!(again.err().map(|e| e.code), Some(ErrorCode::NotFound));
+    }
+
+    #[test]
+    fn tracks_are_stored_normalized() {
+        let (store, submission) = store_with_submission();
+
+        let tracked = store
+            .set_submission_track(submission.uuid, "  DeFi ".to_string())
+            .unwrap();
+
+        assert_eq!(tracked.track, "defi");
+    }
+
+    #[test]
+    fn score_normalization_is_fixed_once_results_are_published() {
+        let (store, submission) = store_with_submission();
+        let id = submission.event_id;
+        for phase in [
+            EventPhase::SubmissionsOpen,
+            EventPhase::Judging,
+            EventPhase::ResultsPublished,
+        ] {
+            store.transition_event(id, phase).unwrap();
+        }
+
+        let res = store.set_event_score_normalization(id, false);
+
+        assert_eq!(res.err().map(|e| e.code), Some(ErrorCode::Locked));
+        assert!(store.get_event(id).unwrap().normalize_scores);
+    }
+}
@@ -0,0 +1,604 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::db;
+use crate::error::{Error, ErrorCode, Result};
+use crate::search;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+    /// Runs before `sql`, failing with a description of the rows the
+    /// migration cannot be applied to.
+    pub precheck: Option<fn(&Connection) -> Result<()>>,
+    /// Runs after `sql`, in the same transaction, for data changes that
+    /// need Rust (such as parsing dates) rather than plain SQL.
+    pub backfill: Option<fn(&Connection) -> Result<()>>,
+}
+
+// Version of the migration that makes submit_by unique per event.
+pub const UNIQUE_SUBMISSIONS_VERSION: i64 = 9;
+
+// Forward-only, applied in order. Never edit a migration that has shipped;
+// append a new one instead so already deployed databases pick it up.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create_initial_tables",
+        sql: "
+      create table if not exists users (
+        near_address TEXT unique not null primary key,
+        email TEXT unique not null,
+        first_name TEXT default null,
+        last_name TEXT default null,
+        is_student INTEGER,
+        country TEXT default null,
+        git_handler TEXT default null,
+        linkedin_handler TEXT default null,
+        twitter_handler TEXT default null
+      ) without rowid;
+
+      create table if not exists events (
+        id INTEGER not null primary key AUTOINCREMENT, 
+        type TEXT not null,
+        title TEXT not null,
+        start_date TEXT not null,
+        end_date TEXT default null,
+        logo TEXT not null,
+        status INTEGER not null
+      );
+
+      create table if not exists submissions (
+        uuid INTEGER not null primary key AUTOINCREMENT, 
+        event_id INTEGER not null,
+        project_name TEXT not null,
+        description TEXT not null,
+        thumbnail TEXT default null,
+        git_url TEXT not null,
+        live_demo_url TEXT default null,
+        video_demo_url TEXT not null,
+        submit_by TEXT not null,
+        status INTEGER not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        FOREIGN KEY (submit_by) REFERENCES users,
+        FOREIGN KEY (event_id) REFERENCES events
+      );
+
+      create table if not exists submission_team (
+        uuid INTEGER not null primary key AUTOINCREMENT, 
+        near_address TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 2,
+        name: "create_roles",
+        sql: "
+      create table if not exists roles (
+        peer_id TEXT not null,
+        role TEXT not null,
+        granted_by TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (peer_id, role)
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 3,
+        name: "create_near_ownership",
+        sql: "
+      create table if not exists near_challenges (
+        near_address TEXT not null,
+        peer_id TEXT not null,
+        nonce TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (near_address, peer_id)
+      );
+
+      create table if not exists peer_bindings (
+        peer_id TEXT not null primary key,
+        near_address TEXT unique not null,
+        public_key TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 4,
+        name: "create_submission_teams",
+        sql: "
+      drop table if exists submission_team;
+
+      create table submission_team (
+        submission_id INTEGER not null,
+        near_address TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (submission_id, near_address),
+        FOREIGN KEY (submission_id) REFERENCES submissions,
+        FOREIGN KEY (near_address) REFERENCES users
+      );
+
+      alter table events add column max_team_size INTEGER not null default 5;
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 5,
+        name: "create_team_invitations",
+        sql: "
+      create table if not exists team_invitations (
+        id INTEGER not null primary key AUTOINCREMENT,
+        submission_id INTEGER not null,
+        invitee TEXT not null,
+        invited_by TEXT not null,
+        status INTEGER not null,
+        expires_at DATETIME not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        FOREIGN KEY (submission_id) REFERENCES submissions,
+        FOREIGN KEY (invitee) REFERENCES users
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 6,
+        name: "event_lifecycle_phases",
+        // live (1) events had submissions open, closed (2) ones stopped
+        // taking submissions and move to judging
+        sql: "
+      update events set status = case status when 1 then 3 when 2 then 4 else status end;
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 7,
+        name: "event_grace_period",
+        sql: "
+      alter table events add column grace_period_minutes INTEGER not null default 0;
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 8,
+        name: "create_submission_revisions",
+        // existing submissions start their history from their current state
+        sql: "
+      create table if not exists submission_revisions (
+        submission_id INTEGER not null,
+        revision INTEGER not null,
+        project_name TEXT not null,
+        description TEXT not null,
+        thumbnail TEXT default null,
+        git_url TEXT not null,
+        live_demo_url TEXT default null,
+        video_demo_url TEXT not null,
+        changed_fields TEXT not null default '',
+        edited_by TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (submission_id, revision),
+        FOREIGN KEY (submission_id) REFERENCES submissions
+      );
+
+      insert into submission_revisions (submission_id, revision, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, edited_by, created_at)
+      select uuid, 1, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, '', created_at
+      from submissions;
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 9,
+        name: "unique_submission_per_event",
+        // refuses to run, leaving the schema at version 8, while a user owns
+        // several submissions for one event; migrate_service lists them in
+        // `duplicate_submissions` so they can be merged or removed first
+        sql: "
+      create unique index if not exists submissions_event_submit_by
+      on submissions (event_id, submit_by);
+
+      create trigger if not exists submissions_owner_not_in_team
+      before insert on submissions
+      when exists (
+        select 1 from submission_team t
+        join submissions s on s.uuid = t.submission_id
+        where t.near_address = NEW.submit_by AND s.event_id = NEW.event_id
+      )
+      begin
+        select raise(abort, 'User already has a submission for this event');
+      end;
+
+      create trigger if not exists submission_team_one_per_event
+      before insert on submission_team
+      when exists (
+        select 1 from submissions s
+        where s.event_id = (select event_id from submissions where uuid = NEW.submission_id)
+        AND (
+          s.submit_by = NEW.near_address
+          OR s.uuid in (select submission_id from submission_team where near_address = NEW.near_address)
+        )
+      )
+      begin
+        select raise(abort, 'User already has a submission for this event');
+      end;
+      ",
+        precheck: Some(check_unique_submissions),
+        backfill: None,
+    },
+    Migration {
+        version: 10,
+        name: "event_timestamps",
+        sql: "
+      alter table events add column starts_at INTEGER default null;
+      alter table events add column ends_at INTEGER default null;
+
+      create index if not exists events_starts_at on events (starts_at);
+      create index if not exists submissions_created_at on submissions (created_at);
+      ",
+        precheck: None,
+        backfill: Some(backfill_event_timestamps),
+    },
+    Migration {
+        version: 11,
+        name: "create_search_index",
+        sql: "
+      create table if not exists search_index (
+        entity TEXT not null,
+        entity_id TEXT not null,
+        token TEXT not null,
+        weight INTEGER not null,
+        primary key (entity, entity_id, token)
+      );
+
+      create index if not exists search_index_token on search_index (entity, token);
+      ",
+        precheck: None,
+        backfill: Some(search::rebuild_index),
+    },
+    Migration {
+        version: 12,
+        name: "create_judging",
+        sql: "
+      create table if not exists event_judges (
+        event_id INTEGER not null,
+        near_address TEXT not null,
+        assigned_by TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (event_id, near_address),
+        FOREIGN KEY (event_id) REFERENCES events,
+        FOREIGN KEY (near_address) REFERENCES users
+      );
+
+      create table if not exists rubric_criteria (
+        id INTEGER not null primary key AUTOINCREMENT,
+        event_id INTEGER not null,
+        name TEXT not null,
+        description TEXT default null,
+        weight REAL not null,
+        max_score INTEGER not null,
+        FOREIGN KEY (event_id) REFERENCES events
+      );
+
+      create table if not exists reviews (
+        submission_id INTEGER not null,
+        judge TEXT not null,
+        comment TEXT default null,
+        total REAL not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        updated_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (submission_id, judge),
+        FOREIGN KEY (submission_id) REFERENCES submissions
+      );
+
+      create table if not exists review_scores (
+        submission_id INTEGER not null,
+        judge TEXT not null,
+        criterion_id INTEGER not null,
+        score INTEGER not null,
+        primary key (submission_id, judge, criterion_id),
+        FOREIGN KEY (criterion_id) REFERENCES rubric_criteria
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 13,
+        name: "create_leaderboard",
+        sql: "
+      alter table events add column normalize_scores INTEGER not null default 1;
+
+      create table if not exists leaderboard_entries (
+        event_id INTEGER not null,
+        rank INTEGER not null,
+        submission_id INTEGER not null,
+        project_name TEXT not null,
+        submit_by TEXT not null,
+        raw_score REAL not null,
+        normalized_score REAL not null,
+        review_count INTEGER not null,
+        frozen_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (event_id, rank),
+        FOREIGN KEY (event_id) REFERENCES events
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 14,
+        name: "create_judge_conflicts",
+        sql: "
+      create table if not exists user_organizations (
+        near_address TEXT not null,
+        organization TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (near_address, organization),
+        FOREIGN KEY (near_address) REFERENCES users
+      );
+
+      create index if not exists user_organizations_organization
+      on user_organizations (organization);
+
+      create table if not exists judge_conflicts (
+        event_id INTEGER not null,
+        submission_id INTEGER not null,
+        judge TEXT not null,
+        reason TEXT not null,
+        detail TEXT not null,
+        detected_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (submission_id, judge),
+        FOREIGN KEY (submission_id) REFERENCES submissions
+      );
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 15,
+        name: "create_review_assignments",
+        sql: "
+      alter table submissions add column track TEXT default null;
+
+      create table if not exists judge_tracks (
+        event_id INTEGER not null,
+        near_address TEXT not null,
+        track TEXT not null,
+        primary key (event_id, near_address, track)
+      );
+
+      create table if not exists review_assignments (
+        event_id INTEGER not null,
+        submission_id INTEGER not null,
+        judge TEXT not null,
+        assigned_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (submission_id, judge),
+        FOREIGN KEY (event_id) REFERENCES events,
+        FOREIGN KEY (submission_id) REFERENCES submissions
+      );
+
+      create index if not exists review_assignments_judge on review_assignments (judge);
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 16,
+        name: "create_near_access_keys",
+        // named accounts used to pin whichever key was proven first, which
+        // proves nothing; those bindings are dropped and have to be proven
+        // again with an attested key
+        sql: "
+      create table if not exists near_access_keys (
+        near_address TEXT not null,
+        public_key TEXT not null,
+        attested_by TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        primary key (near_address, public_key)
+      );
+
+      delete from peer_bindings
+      where not (length(near_address) = 64 AND near_address not glob '*[^0-9a-fA-F]*');
+      ",
+        precheck: None,
+        backfill: None,
+    },
+    Migration {
+        version: 17,
+        name: "judge_roles",
+        // scoring now needs the judge role; judges already attached to an
+        // event keep working. The participant role never gated anything.
+        sql: "
+      insert or ignore into roles (peer_id, role, granted_by)
+      select b.peer_id, 'judge', j.assigned_by
+      from event_judges j
+      join peer_bindings b on b.near_address = j.near_address;
+
+      delete from roles where role = 'participant';
+      ",
+        precheck: None,
+        backfill: None,
+    },
+];
+
+/// A user owning several submissions for one event, which keeps
+/// `UNIQUE_SUBMISSIONS_VERSION` from being applied.
+#[marine]
+#[derive(Default)]
+pub struct DuplicateSubmission {
+    pub event_id: i64,
+    pub submit_by: String,
+    pub submission_ids: Vec<i64>,
+}
+
+pub fn get_duplicate_submissions(conn: &Connection) -> Result<Vec<DuplicateSubmission>> {
+    db::query_all(
+        conn,
+        "
+        select event_id, submit_by, group_concat(uuid) from (
+          select * from submissions order by uuid
+        )
+        group by event_id, submit_by
+        having count(*) > 1
+        order by event_id, submit_by;
+        ",
+        &[],
+        |row| {
+            Ok(DuplicateSubmission {
+                event_id: row[0].as_integer().unwrap_or_default(),
+                submit_by: row[1].as_string().unwrap_or_default().to_string(),
+                submission_ids: row[2]
+                    .as_string()
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter_map(|id| id.parse().ok())
+                    .collect(),
+            })
+        },
+    )
+}
+
+fn check_unique_submissions(conn: &Connection) -> Result<()> {
+    let duplicates = get_duplicate_submissions(conn)?;
+    if duplicates.is_empty() {
+        return Ok(());
+    }
+
+    let pairs: Vec<String> = duplicates
+        .iter()
+        .map(|d| format!("event {} by {}", d.event_id, d.submit_by))
+        .collect();
+
+    Err(Error::new(
+        ErrorCode::Conflict,
+        format!(
+            "Users own several submissions for one event, resolve them first: {}",
+            pairs.join(", ")
+        ),
+    ))
+}
+
+fn backfill_event_timestamps(conn: &Connection) -> Result<()> {
+    for event in db::get_events(conn)? {
+        db::set_event_timestamps(conn, event.id, &event.start_date, &event.end_date)?;
+    }
+
+    Ok(())
+}
+
+#[marine]
+#[derive(Default)]
+pub struct MigrationResult {
+    pub version: i64,
+    pub applied: Vec<i64>,
+    pub duplicate_submissions: Vec<DuplicateSubmission>,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl MigrationResult {
+    pub fn from_res(res: Result<Vec<i64>>, version: i64) -> MigrationResult {
+        match res {
+            Ok(applied) => MigrationResult {
+                version,
+                applied,
+                duplicate_submissions: Vec::new(),
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => MigrationResult {
+                version,
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+pub fn create_version_table(conn: &Connection) -> Result<()> {
+    Ok(conn.execute(
+        "
+      create table if not exists schema_version (
+        version INTEGER not null primary key,
+        name TEXT not null,
+        applied_at DATETIME default CURRENT_TIMESTAMP
+      );
+      ",
+    )?)
+}
+
+/// Highest applied migration, or 0 for a fresh database.
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    create_version_table(conn)?;
+
+    db::query_one(
+        conn,
+        "select coalesce(max(version), 0) from schema_version;",
+        &[],
+        |row| row[0].as_integer().ok_or_else(db::get_none_error),
+    )
+}
+
+/// Applies every pending migration in order and returns the versions applied
+/// by this call. Each migration runs in its own transaction together with its
+/// `schema_version` row, so a failure leaves the database at the previous
+/// version.
+pub fn migrate(conn: &Connection) -> Result<Vec<i64>> {
+    let current = current_version(conn)?;
+    let mut applied = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let res = db::transaction(conn, || {
+            if let Some(precheck) = migration.precheck {
+                precheck(conn)?;
+            }
+            conn.execute(migration.sql)?;
+            if let Some(backfill) = migration.backfill {
+                backfill(conn)?;
+            }
+
+            db::execute(
+                conn,
+                "insert into schema_version (version, name) values (?, ?);",
+                &[
+                    Value::Integer(migration.version),
+                    Value::String(migration.name.to_string()),
+                ],
+            )
+        });
+
+        if let Err(e) = res {
+            log::error!(
+                "migration {} ({}) failed: {}",
+                migration.version,
+                migration.name,
+                e
+            );
+            return Err(e);
+        }
+
+        log::info!(
+            "applied migration {} ({})",
+            migration.version,
+            migration.name
+        );
+        applied.push(migration.version);
+    }
+
+    Ok(applied)
+}
@@ -0,0 +1,52 @@
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+
+use crate::error::{Error, ErrorCode, Result};
+
+const ED25519_PREFIX: &str = "ed25519:";
+
+fn get_key_error(msg: &str) -> Error {
+    Error::new(ErrorCode::Validation, msg.to_string())
+}
+
+fn decode_base58(value: &str) -> Result<Vec<u8>> {
+    let encoded = value.strip_prefix(ED25519_PREFIX).unwrap_or(value);
+    bs58::decode(encoded)
+        .into_vec()
+        .map_err(|_| get_key_error("Invalid base58 encoding"))
+}
+
+/// Parses a NEAR formatted public key, `ed25519:<base58>`.
+pub fn parse_public_key(public_key: &str) -> Result<PublicKey> {
+    if !public_key.starts_with(ED25519_PREFIX) {
+        return Err(get_key_error("Only ed25519 public keys are supported"));
+    }
+
+    let bytes = decode_base58(public_key)?;
+    PublicKey::from_bytes(&bytes).map_err(|_| get_key_error("Invalid public key"))
+}
+
+/// Parses a base58 signature, with or without the `ed25519:` prefix.
+pub fn parse_signature(signature: &str) -> Result<Signature> {
+    let bytes = decode_base58(signature)?;
+    Signature::try_from(bytes.as_slice()).map_err(|_| get_key_error("Invalid signature"))
+}
+
+pub fn verify_signature(public_key: &str, message: &str, signature: &str) -> Result<()> {
+    let key = parse_public_key(public_key)?;
+    let signature = parse_signature(signature)?;
+
+    key.verify(message.as_bytes(), &signature)
+        .map_err(|_| Error::new(ErrorCode::Unauthorized, "Signature verification failed"))
+}
+
+/// Implicit accounts are the hex encoded ed25519 public key itself.
+pub fn is_implicit_account(near_address: &str) -> bool {
+    near_address.len() == 64 && near_address.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub fn implicit_account_matches(near_address: &str, public_key: &str) -> bool {
+    match parse_public_key(public_key) {
+        Ok(key) => hex::encode(key.as_bytes()) == near_address.to_lowercase(),
+        Err(_) => false,
+    }
+}
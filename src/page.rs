@@ -0,0 +1,209 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+use serde_json::json;
+
+use crate::db;
+use crate::error::{Error, ErrorCode, Result};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 20;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+/// Paging and ordering for the `list_*` functions. A zero `limit` uses
+/// `DEFAULT_PAGE_SIZE`, an empty `sort_by` the listing's default order and an
+/// empty `cursor` starts from the first page.
+#[marine]
+#[derive(Default)]
+pub struct PageRequest {
+    pub limit: i64,
+    pub cursor: String,
+    pub sort_by: String,
+    pub descending: bool,
+}
+
+/// A column a listing can be ordered by. `expr` must never contain caller
+/// input, it is written into the statement as is.
+pub struct SortColumn {
+    pub name: &'static str,
+    pub expr: &'static str,
+}
+
+/// Where clause fragments and the values bound to their placeholders.
+#[derive(Default)]
+pub struct Filters {
+    conditions: Vec<String>,
+    params: Vec<Value>,
+}
+
+impl Filters {
+    pub fn add(&mut self, condition: &str, params: Vec<Value>) {
+        self.conditions.push(condition.to_string());
+        self.params.extend(params);
+    }
+
+    fn where_clause(&self, extra: Option<&str>) -> String {
+        let mut conditions: Vec<&str> = self.conditions.iter().map(|c| c.as_str()).collect();
+        if let Some(extra) = extra {
+            conditions.push(extra);
+        }
+
+        if conditions.is_empty() {
+            "".to_string()
+        } else {
+            format!("where {}", conditions.join(" AND "))
+        }
+    }
+}
+
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: String,
+    pub total: i64,
+}
+
+fn get_cursor_error() -> Error {
+    Error::new(ErrorCode::Validation, "Invalid page cursor")
+}
+
+fn to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::Integer(v) => json!(v),
+        Value::Float(v) => json!(v),
+        Value::String(v) => json!(v),
+        _ => serde_json::Value::Null,
+    }
+}
+
+fn from_json(value: &serde_json::Value) -> Result<Value> {
+    match value {
+        serde_json::Value::Number(n) if n.is_i64() => {
+            Ok(Value::Integer(n.as_i64().unwrap_or_default()))
+        }
+        serde_json::Value::Number(n) => Ok(Value::Float(n.as_f64().unwrap_or_default())),
+        serde_json::Value::String(s) => Ok(Value::String(s.clone())),
+        _ => Err(get_cursor_error()),
+    }
+}
+
+/// Cursors are the sort column, sort value and key of the last row of a
+/// page, hex encoded so callers treat them as opaque.
+fn encode_cursor(sort_by: &str, value: &Value, key: &Value) -> String {
+    let cursor = json!({ "s": sort_by, "v": to_json(value), "k": to_json(key) });
+    hex::encode(cursor.to_string())
+}
+
+fn decode_cursor(cursor: &str, sort_by: &str) -> Result<(Value, Value)> {
+    let bytes = hex::decode(cursor).map_err(|_| get_cursor_error())?;
+    let cursor: serde_json::Value =
+        serde_json::from_slice(&bytes).map_err(|_| get_cursor_error())?;
+
+    if cursor["s"] != json!(sort_by) {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Page cursor belongs to a different sort order",
+        ));
+    }
+
+    Ok((from_json(&cursor["v"])?, from_json(&cursor["k"])?))
+}
+
+/// Runs a keyset paginated `select *` over `table`. Rows are ordered by the
+/// requested sort column, ties broken by `key`, and handed to `from_row`
+/// with the sort value and key appended after the table's own columns.
+pub fn fetch_page<T>(
+    conn: &Connection,
+    table: &str,
+    key: &str,
+    sort_columns: &[SortColumn],
+    filters: Filters,
+    request: &PageRequest,
+    from_row: fn(&[Value]) -> Result<T>,
+) -> Result<Page<T>> {
+    let sort = if request.sort_by.is_empty() {
+        &sort_columns[0]
+    } else {
+        sort_columns
+            .iter()
+            .find(|c| c.name == request.sort_by)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorCode::Validation,
+                    format!("Cannot sort by {}", request.sort_by),
+                )
+            })?
+    };
+
+    let limit = match request.limit {
+        0 => DEFAULT_PAGE_SIZE,
+        l if (1..=MAX_PAGE_SIZE).contains(&l) => l,
+        _ => {
+            return Err(Error::new(
+                ErrorCode::Validation,
+                format!("Page limit must be between 1 and {}", MAX_PAGE_SIZE),
+            ))
+        }
+    };
+
+    let total = db::query_one(
+        conn,
+        &format!(
+            "select count(*) from {} {};",
+            table,
+            filters.where_clause(None)
+        ),
+        &filters.params,
+        |row| Ok(row[0].as_integer().unwrap_or_default()),
+    )?;
+
+    let (direction, comparison) = if request.descending {
+        ("desc", "<")
+    } else {
+        ("asc", ">")
+    };
+    let sort_expr = format!("coalesce({}, '')", sort.expr);
+
+    let mut params = filters.params.clone();
+    let after = if request.cursor.is_empty() {
+        None
+    } else {
+        let (value, last_key) = decode_cursor(&request.cursor, sort.name)?;
+        params.extend(vec![value.clone(), value, last_key]);
+        Some(format!(
+            "({sort} {cmp} ? OR ({sort} = ? AND {key} {cmp} ?))",
+            sort = sort_expr,
+            cmp = comparison,
+            key = key
+        ))
+    };
+    params.push(Value::Integer(limit + 1));
+
+    let sql = format!(
+        "select *, {sort} as page_sort, {key} as page_key from {table} {filter} order by page_sort {dir}, page_key {dir} limit ?;",
+        sort = sort_expr,
+        key = key,
+        table = table,
+        filter = filters.where_clause(after.as_deref()),
+        dir = direction
+    );
+
+    let mut rows = db::query_all(conn, &sql, &params, |row| Ok(row.to_vec()))?;
+
+    let mut next_cursor = "".to_string();
+    if rows.len() as i64 > limit {
+        rows.truncate(limit as usize);
+        if let Some(last) = rows.last() {
+            let n = last.len();
+            next_cursor = encode_cursor(sort.name, &last[n - 2], &last[n - 1]);
+        }
+    }
+
+    let items = rows
+        .iter()
+        .map(|row| from_row(row))
+        .collect::<Result<Vec<T>>>()?;
+
+    Ok(Page {
+        items,
+        next_cursor,
+        total,
+    })
+}
@@ -0,0 +1,216 @@
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::backup;
+use crate::db;
+use crate::error::{Error, ErrorCode, Result};
+
+// How long a reset token can be confirmed.
+const RESET_TOKEN_TTL: &str = "-10 minutes";
+
+#[marine]
+#[derive(Default)]
+pub struct ResetResult {
+    pub token: String,
+    pub archive_id: i64,
+    pub done: bool,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl ResetResult {
+    pub fn from_res(res: Result<ResetResult>) -> ResetResult {
+        match res {
+            Ok(v) => v,
+            Err(e) => ResetResult {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[marine]
+#[derive(Default)]
+pub struct ResetArchive {
+    pub id: i64,
+    pub schema_version: i64,
+    pub rows: i64,
+    pub created_at: String,
+}
+
+impl ResetArchive {
+    pub fn from_row(row: &[Value]) -> Result<ResetArchive> {
+        Ok(ResetArchive {
+            id: row[0].as_integer().ok_or(db::get_none_error())?,
+            schema_version: row[1].as_integer().unwrap_or_default(),
+            rows: row[2].as_integer().unwrap_or_default(),
+            created_at: row[3].as_string().unwrap_or_default().to_string(),
+        })
+    }
+}
+
+/// The archive and token tables live outside the migrations, as they have
+/// to survive the reset that drops everything else.
+pub fn create_reset_tables(conn: &Connection) -> Result<()> {
+    Ok(conn.execute(
+        "
+      create table if not exists reset_archives (
+        id INTEGER not null primary key AUTOINCREMENT,
+        schema_version INTEGER not null,
+        row_count INTEGER not null,
+        created_at DATETIME default CURRENT_TIMESTAMP,
+        dump TEXT not null
+      );
+
+      create table if not exists reset_requests (
+        token TEXT not null primary key,
+        archive_id INTEGER not null,
+        requested_by TEXT not null,
+        created_at DATETIME default CURRENT_TIMESTAMP
+      );
+      ",
+    )?)
+}
+
+/// First step of a reset: archives a full backup and returns the token
+/// that confirms it. Only the latest token is valid.
+pub fn request_reset(conn: &Connection, requested_by: String) -> Result<ResetResult> {
+    create_reset_tables(conn)?;
+    let dump = backup::backup(conn)?;
+
+    db::execute(
+        conn,
+        "insert into reset_archives (schema_version, row_count, dump) values (?, ?, ?);",
+        &[
+            Value::Integer(dump.schema_version),
+            Value::Integer(dump.rows),
+            Value::String(dump.dump),
+        ],
+    )?;
+    let archive_id = db::last_insert_rowid(conn)?;
+
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|_| Error::new(ErrorCode::Internal, "Unable to generate reset token"))?;
+    let token = hex::encode(bytes);
+
+    db::execute(conn, "delete from reset_requests;", &[])?;
+    db::execute(
+        conn,
+        "insert into reset_requests (token, archive_id, requested_by) values (?, ?, ?);",
+        &[
+            Value::String(token.clone()),
+            Value::Integer(archive_id),
+            Value::String(requested_by),
+        ],
+    )?;
+
+    log::warn!("reset requested, data archived as {}", archive_id);
+
+    Ok(ResetResult {
+        token,
+        archive_id,
+        done: false,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
+
+/// Second step of a reset: drops every table once `token` matches an
+/// unexpired request made by the same peer. The archive taken with the
+/// request is refreshed first, so writes made while the token was pending
+/// are kept too.
+pub fn confirm_reset(
+    conn: &Connection,
+    token: String,
+    requested_by: String,
+) -> Result<ResetResult> {
+    create_reset_tables(conn)?;
+
+    let archive_id = db::query_one(
+        conn,
+        "
+        select archive_id from reset_requests
+        where token = ? AND requested_by = ? AND created_at >= datetime('now', ?);
+        ",
+        &[
+            Value::String(token),
+            Value::String(requested_by),
+            Value::String(RESET_TOKEN_TTL.to_string()),
+        ],
+        |row| row[0].as_integer().ok_or_else(db::get_none_error),
+    )
+    .map_err(|_| {
+        Error::new(
+            ErrorCode::Unauthorized,
+            "Reset token is invalid or expired, request a new one",
+        )
+    })?;
+
+    db::execute(conn, "delete from reset_requests;", &[])?;
+
+    let dump = backup::backup(conn)?;
+    db::execute(
+        conn,
+        "
+        update reset_archives
+        set schema_version = ?, row_count = ?, dump = ?, created_at = CURRENT_TIMESTAMP
+        where id = ?;
+        ",
+        &[
+            Value::Integer(dump.schema_version),
+            Value::Integer(dump.rows),
+            Value::String(dump.dump),
+            Value::Integer(archive_id),
+        ],
+    )?;
+
+    db::delete_tables(conn)?;
+
+    log::warn!("service reset, previous data is in archive {}", archive_id);
+
+    Ok(ResetResult {
+        token: "".to_string(),
+        archive_id,
+        done: true,
+        err_msg: "".to_string(),
+        err_code: "".to_string(),
+        success: true,
+    })
+}
+
+pub fn get_archives(conn: &Connection) -> Result<Vec<ResetArchive>> {
+    create_reset_tables(conn)?;
+
+    db::query_all(
+        conn,
+        "select id, schema_version, row_count, created_at from reset_archives order by id desc;",
+        &[],
+        ResetArchive::from_row,
+    )
+}
+
+/// The dump an archive holds, in the form `restore_service` takes.
+pub fn get_archive(conn: &Connection, id: i64) -> Result<backup::Backup> {
+    create_reset_tables(conn)?;
+
+    db::query_one(
+        conn,
+        "select schema_version, row_count, dump from reset_archives where id = ?;",
+        &[Value::Integer(id)],
+        |row| {
+            Ok(backup::Backup {
+                schema_version: row[0].as_integer().unwrap_or_default(),
+                rows: row[1].as_integer().unwrap_or_default(),
+                dump: row[2].as_string().unwrap_or_default().to_string(),
+                success: true,
+                ..Default::default()
+            })
+        },
+    )
+}
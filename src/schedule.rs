@@ -0,0 +1,68 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::DateTime;
+
+use crate::db::Event;
+use crate::error::{Error, ErrorCode, Result};
+
+fn get_date_error(msg: String) -> Error {
+    Error::new(ErrorCode::Validation, msg)
+}
+
+/// Current unix time in seconds, read from the host clock through WASI. A
+/// clock before the epoch is an error rather than 0, which would make every
+/// event look like it has not started.
+pub fn now() -> Result<i64> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .map_err(|_| Error::new(ErrorCode::Internal, "Unable to read the system clock"))
+}
+
+/// Parses an RFC 3339 timestamp into unix seconds. Offsets without a colon
+/// (`2022-12-05T00:00:00+0000`) are accepted too, as existing events were
+/// created with them.
+pub fn parse_timestamp(date: &str) -> Result<i64> {
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .map(|d| d.timestamp())
+        .map_err(|_| get_date_error(format!("Invalid RFC 3339 date: {}", date)))
+}
+
+/// `start_date` is required, an empty `end_date` leaves the event open ended.
+pub fn validate_event_dates(start_date: &str, end_date: &str) -> Result<()> {
+    let start = parse_timestamp(start_date)?;
+
+    if !end_date.is_empty() && parse_timestamp(end_date)? <= start {
+        return Err(get_date_error(
+            "Event end_date must be after start_date".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Fails unless now falls between the event's start and its end plus the
+/// grace period.
+pub fn require_within_deadline(event: &Event) -> Result<()> {
+    let now = now()?;
+
+    if now < parse_timestamp(&event.start_date)? {
+        return Err(Error::new(
+            ErrorCode::NotStarted,
+            "Event has not started yet",
+        ));
+    }
+
+    if !event.end_date.is_empty() {
+        let deadline = parse_timestamp(&event.end_date)? + event.grace_period_minutes * 60;
+        if now > deadline {
+            return Err(Error::new(
+                ErrorCode::DeadlinePassed,
+                "Submission deadline has passed",
+            ));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+
+use marine_rs_sdk::marine;
+use marine_sqlite_connector::{Connection, Value};
+
+use crate::db::{self, Submission, User};
+use crate::error::{Error, ErrorCode, Result};
+
+// Search runs over the `search_index` table rather than an FTS5 virtual
+// table, so it does not depend on how the bundled sqlite3.wasm was built.
+// Every indexed field is split into lowercase tokens, each stored with a
+// weight, and a query matches a row when every query token is a prefix of
+// one of its tokens.
+
+pub const SEARCH_PAGE_SIZE: i64 = 20;
+pub const MAX_USER_RESULTS: i64 = 50;
+
+// Queries longer than this are cut, keeping the generated statement bounded.
+const MAX_QUERY_TOKENS: usize = 8;
+
+const SUBMISSION_ENTITY: &str = "submission";
+const USER_ENTITY: &str = "user";
+
+#[marine]
+#[derive(Default)]
+pub struct SubmissionHit {
+    pub submission: Submission,
+    pub score: i64,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct SubmissionSearchResult {
+    pub hits: Vec<SubmissionHit>,
+    pub total: i64,
+    pub page: i64,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl SubmissionSearchResult {
+    pub fn from_res(res: Result<(Vec<SubmissionHit>, i64)>, page: i64) -> SubmissionSearchResult {
+        match res {
+            Ok((hits, total)) => SubmissionSearchResult {
+                hits,
+                total,
+                page,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => SubmissionSearchResult {
+                page,
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[marine]
+#[derive(Default)]
+pub struct UserHit {
+    pub user: User,
+    pub score: i64,
+}
+
+#[marine]
+#[derive(Default)]
+pub struct UserSearchResult {
+    pub hits: Vec<UserHit>,
+    pub err_msg: String,
+    pub err_code: String,
+    pub success: bool,
+}
+
+impl UserSearchResult {
+    pub fn from_res(res: Result<Vec<UserHit>>) -> UserSearchResult {
+        match res {
+            Ok(hits) => UserSearchResult {
+                hits,
+                err_msg: "".to_string(),
+                err_code: "".to_string(),
+                success: true,
+            },
+            Err(e) => UserSearchResult {
+                err_msg: e.to_string(),
+                err_code: e.code.as_str().to_string(),
+                success: false,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Lowercase alphanumeric runs of at least two characters.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() >= 2)
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Replaces the index rows of one entity. `fields` pairs each text with the
+/// weight its tokens count for; a token found in several fields, or several
+/// times, adds up.
+fn index_entity(
+    conn: &Connection,
+    entity: &str,
+    entity_id: String,
+    fields: &[(&str, i64)],
+) -> Result<()> {
+    db::execute(
+        conn,
+        "delete from search_index where entity = ? AND entity_id = ?;",
+        &[
+            Value::String(entity.to_string()),
+            Value::String(entity_id.clone()),
+        ],
+    )?;
+
+    let mut tokens: BTreeMap<String, i64> = BTreeMap::new();
+    for (text, weight) in fields {
+        for token in tokenize(text) {
+            *tokens.entry(token).or_default() += weight;
+        }
+    }
+
+    for (token, weight) in tokens {
+        db::execute(
+            conn,
+            "insert into search_index (entity, entity_id, token, weight) values (?, ?, ?, ?);",
+            &[
+                Value::String(entity.to_string()),
+                Value::String(entity_id.clone()),
+                Value::String(token),
+                Value::Integer(weight),
+            ],
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn index_submission(conn: &Connection, submission: &Submission) -> Result<()> {
+    index_entity(
+        conn,
+        SUBMISSION_ENTITY,
+        submission.uuid.to_string(),
+        &[(&submission.project_name, 5), (&submission.description, 1)],
+    )
+}
+
+pub fn index_user(conn: &Connection, user: &User) -> Result<()> {
+    index_entity(
+        conn,
+        USER_ENTITY,
+        user.near_address.clone(),
+        &[
+            (&user.first_name, 3),
+            (&user.last_name, 3),
+            (&user.git_handler, 2),
+            (&user.near_address, 1),
+        ],
+    )
+}
+
+/// Indexes every existing submission and user, used when the index table is
+/// first created.
+pub fn rebuild_index(conn: &Connection) -> Result<()> {
+    db::execute(conn, "delete from search_index;", &[])?;
+
+    for submission in db::get_submissions(conn)? {
+        index_submission(conn, &submission)?;
+    }
+
+    for user in db::query_all(conn, "select * from users;", &[], User::from_row)? {
+        index_user(conn, &user)?;
+    }
+
+    Ok(())
+}
+
+/// Builds a `(entity_id, score)` subquery over the entities matching every
+/// token of `query`. An exact token match scores double a prefix match.
+fn matches(entity: &str, query: &str) -> Result<(String, Vec<Value>)> {
+    let mut terms = tokenize(query);
+    terms.dedup();
+    terms.truncate(MAX_QUERY_TOKENS);
+
+    if terms.is_empty() {
+        return Err(Error::new(
+            ErrorCode::Validation,
+            "Search query must contain at least one word of two or more characters",
+        ));
+    }
+
+    let mut selects = Vec::new();
+    let mut params = Vec::new();
+    for (i, term) in terms.iter().enumerate() {
+        selects.push(format!(
+            "select entity_id, {} as term, case when token = ? then weight * 2 else weight end as score
+             from search_index where entity = ? AND substr(token, 1, ?) = ?",
+            i
+        ));
+        params.extend(vec![
+            Value::String(term.clone()),
+            Value::String(entity.to_string()),
+            Value::Integer(term.chars().count() as i64),
+            Value::String(term.clone()),
+        ]);
+    }
+
+    let sql = format!(
+        "select entity_id, sum(score) as score from ({}) group by entity_id having count(distinct term) = {}",
+        selects.join(" union all "),
+        terms.len()
+    );
+
+    Ok((sql, params))
+}
+
+/// Ranked submissions matching `query`, optionally limited to one event
+/// (`event_id` 0 searches all). `page` starts at 1.
+pub fn search_submissions(
+    conn: &Connection,
+    query: &str,
+    event_id: i64,
+    page: i64,
+) -> Result<(Vec<SubmissionHit>, i64)> {
+    if page < 1 {
+        return Err(Error::new(ErrorCode::Validation, "Page must be at least 1"));
+    }
+
+    let (hits, mut params) = matches(SUBMISSION_ENTITY, query)?;
+    params.extend(vec![Value::Integer(event_id), Value::Integer(event_id)]);
+
+    let from = format!(
+        "from submissions s join ({}) hits on hits.entity_id = cast(s.uuid as text)
+         where (? = 0 OR s.event_id = ?)",
+        hits
+    );
+
+    let total = db::query_one(
+        conn,
+        &format!("select count(*) {};", from),
+        &params,
+        |row| Ok(row[0].as_integer().unwrap_or_default()),
+    )?;
+
+    params.extend(vec![
+        Value::Integer(SEARCH_PAGE_SIZE),
+        Value::Integer((page - 1) * SEARCH_PAGE_SIZE),
+    ]);
+
+    let hits = db::query_all(
+        conn,
+        &format!(
+            "select s.*, hits.score {} order by hits.score desc, s.uuid asc limit ? offset ?;",
+            from
+        ),
+        &params,
+        |row| {
+            Ok(SubmissionHit {
+                submission: Submission::from_row(row)?,
+                score: row[row.len() - 1].as_integer().unwrap_or_default(),
+            })
+        },
+    )?;
+
+    Ok((hits, total))
+}
+
+/// The best `MAX_USER_RESULTS` users matching `query` by name, git handle
+/// or NEAR address.
+pub fn search_users(conn: &Connection, query: &str) -> Result<Vec<UserHit>> {
+    let (hits, mut params) = matches(USER_ENTITY, query)?;
+    params.push(Value::Integer(MAX_USER_RESULTS));
+
+    db::query_all(
+        conn,
+        &format!(
+            "select u.*, hits.score from users u join ({}) hits on hits.entity_id = u.near_address
+             order by hits.score desc, u.near_address asc limit ?;",
+            hits
+        ),
+        &params,
+        |row| {
+            Ok(UserHit {
+                user: User::from_row(row)?,
+                score: row[row.len() - 1].as_integer().unwrap_or_default(),
+            })
+        },
+    )
+}
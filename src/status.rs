@@ -0,0 +1,229 @@
+use crate::db::{Event, Submission};
+use crate::error::{Error, ErrorCode, Result};
+
+/// Lifecycle of an event, stored as its integer code in `events.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventPhase {
+    Draft,
+    RegistrationOpen,
+    SubmissionsOpen,
+    Judging,
+    ResultsPublished,
+    Archived,
+}
+
+impl EventPhase {
+    pub fn code(&self) -> i64 {
+        match self {
+            EventPhase::Draft => 1,
+            EventPhase::RegistrationOpen => 2,
+            EventPhase::SubmissionsOpen => 3,
+            EventPhase::Judging => 4,
+            EventPhase::ResultsPublished => 5,
+            EventPhase::Archived => 6,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Result<EventPhase> {
+        match code {
+            1 => Ok(EventPhase::Draft),
+            2 => Ok(EventPhase::RegistrationOpen),
+            3 => Ok(EventPhase::SubmissionsOpen),
+            4 => Ok(EventPhase::Judging),
+            5 => Ok(EventPhase::ResultsPublished),
+            6 => Ok(EventPhase::Archived),
+            _ => Err(Error::new(
+                ErrorCode::Internal,
+                format!("Unknown event phase: {}", code),
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventPhase::Draft => "draft",
+            EventPhase::RegistrationOpen => "registration_open",
+            EventPhase::SubmissionsOpen => "submissions_open",
+            EventPhase::Judging => "judging",
+            EventPhase::ResultsPublished => "results_published",
+            EventPhase::Archived => "archived",
+        }
+    }
+
+    pub fn parse(phase: &str) -> Result<EventPhase> {
+        match phase {
+            "draft" => Ok(EventPhase::Draft),
+            "registration_open" => Ok(EventPhase::RegistrationOpen),
+            "submissions_open" => Ok(EventPhase::SubmissionsOpen),
+            "judging" => Ok(EventPhase::Judging),
+            "results_published" => Ok(EventPhase::ResultsPublished),
+            "archived" => Ok(EventPhase::Archived),
+            _ => Err(Error::new(
+                ErrorCode::Validation,
+                format!("Unknown event phase: {}", phase),
+            )),
+        }
+    }
+
+    /// Phases an event may move to next. Events move forward one phase at a
+    /// time, may skip registration, may go back from judging to reopen
+    /// submissions, and can be archived early until judging starts.
+    pub fn next_phases(&self) -> &'static [EventPhase] {
+        match self {
+            EventPhase::Draft => &[
+                EventPhase::RegistrationOpen,
+                EventPhase::SubmissionsOpen,
+                EventPhase::Archived,
+            ],
+            EventPhase::RegistrationOpen => &[EventPhase::SubmissionsOpen, EventPhase::Archived],
+            EventPhase::SubmissionsOpen => &[EventPhase::Judging, EventPhase::Archived],
+            EventPhase::Judging => &[EventPhase::SubmissionsOpen, EventPhase::ResultsPublished],
+            EventPhase::ResultsPublished => &[EventPhase::Archived],
+            EventPhase::Archived => &[],
+        }
+    }
+
+    pub fn can_transition_to(&self, next: EventPhase) -> bool {
+        self.next_phases().contains(&next)
+    }
+
+    /// Teams can form and drafts can be edited while registration or
+    /// submissions are open.
+    pub fn accepts_drafts(&self) -> bool {
+        matches!(
+            self,
+            EventPhase::RegistrationOpen | EventPhase::SubmissionsOpen
+        )
+    }
+
+    pub fn accepts_submissions(&self) -> bool {
+        *self == EventPhase::SubmissionsOpen
+    }
+}
+
+fn get_phase_error(event: &Event, action: &str) -> Error {
+    let phase = EventPhase::from_code(event.status)
+        .map(|p| p.as_str())
+        .unwrap_or("unknown");
+
+    Error::new(
+        ErrorCode::InvalidState,
+        format!("Event is {}, {} is not allowed", phase, action),
+    )
+}
+
+pub fn require_drafts_open(event: &Event) -> Result<()> {
+    if EventPhase::from_code(event.status)?.accepts_drafts() {
+        Ok(())
+    } else {
+        Err(get_phase_error(event, "editing submissions"))
+    }
+}
+
+pub fn require_submissions_open(event: &Event) -> Result<()> {
+    if EventPhase::from_code(event.status)?.accepts_submissions() {
+        Ok(())
+    } else {
+        Err(get_phase_error(event, "submitting"))
+    }
+}
+
+/// State of a submission, stored as its integer code in
+/// `submissions.status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubmissionStatus {
+    Draft,
+    Submitted,
+    Withdrawn,
+}
+
+impl SubmissionStatus {
+    pub fn code(&self) -> i64 {
+        match self {
+            SubmissionStatus::Draft => 1,
+            SubmissionStatus::Submitted => 2,
+            SubmissionStatus::Withdrawn => 3,
+        }
+    }
+
+    pub fn from_code(code: i64) -> Result<SubmissionStatus> {
+        match code {
+            1 => Ok(SubmissionStatus::Draft),
+            2 => Ok(SubmissionStatus::Submitted),
+            3 => Ok(SubmissionStatus::Withdrawn),
+            _ => Err(Error::new(
+                ErrorCode::Internal,
+                format!("Unknown submission status: {}", code),
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionStatus::Draft => "draft",
+            SubmissionStatus::Submitted => "submitted",
+            SubmissionStatus::Withdrawn => "withdrawn",
+        }
+    }
+
+    /// Drafts get submitted or withdrawn by their team. Reopening a
+    /// submitted or withdrawn project back to a draft is left to organizers.
+    pub fn can_transition_to(&self, next: SubmissionStatus) -> bool {
+        matches!(
+            (self, next),
+            (SubmissionStatus::Draft, SubmissionStatus::Submitted)
+                | (SubmissionStatus::Draft, SubmissionStatus::Withdrawn)
+                | (SubmissionStatus::Submitted, SubmissionStatus::Withdrawn)
+                | (SubmissionStatus::Submitted, SubmissionStatus::Draft)
+                | (SubmissionStatus::Withdrawn, SubmissionStatus::Draft)
+        )
+    }
+}
+
+pub fn require_draft(submission: &Submission) -> Result<()> {
+    if submission.status == SubmissionStatus::Draft.code() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorCode::Locked,
+            "Submission is no longer a draft",
+        ))
+    }
+}
+
+pub fn require_event_transition(event: &Event, next: EventPhase) -> Result<()> {
+    let current = EventPhase::from_code(event.status)?;
+
+    if current.can_transition_to(next) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorCode::InvalidState,
+            format!(
+                "Event cannot move from {} to {}",
+                current.as_str(),
+                next.as_str()
+            ),
+        ))
+    }
+}
+
+pub fn require_submission_transition(
+    submission: &Submission,
+    next: SubmissionStatus,
+) -> Result<()> {
+    let current = SubmissionStatus::from_code(submission.status)?;
+
+    if current.can_transition_to(next) {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorCode::InvalidState,
+            format!(
+                "Submission cannot move from {} to {}",
+                current.as_str(),
+                next.as_str()
+            ),
+        ))
+    }
+}
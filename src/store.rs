@@ -0,0 +1,237 @@
+use marine_sqlite_connector::Connection;
+
+use crate::db::{self, Event, Submission, TeamMember, User};
+use crate::error::Result;
+use crate::status::{EventPhase, SubmissionStatus};
+
+/// Profile fields a user can edit after registering.
+pub struct UserProfile {
+    pub first_name: String,
+    pub last_name: String,
+    pub is_student: u8,
+    pub country: String,
+    pub git: String,
+    pub linkedin: String,
+    pub twitter: String,
+}
+
+/// Event fields organizers set on creation and can edit later.
+pub struct EventFields {
+    pub title: String,
+    pub event_type: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub logo: String,
+}
+
+/// Project fields a team can edit while its submission is a draft.
+pub struct SubmissionFields {
+    pub name: String,
+    pub description: String,
+    pub thumbnail: String,
+    pub git: String,
+    pub live_url: String,
+    pub video_url: String,
+}
+
+/// Storage for users, events, submissions and their teams. The service runs
+/// on `SqliteStore`; `MemoryStore` keeps the same rules without SQLite so the
+/// workflow can be exercised off the Marine runtime. Invitations, revisions,
+/// paged listings, search and judging are SQL only and stay in their modules.
+pub trait Store {
+    fn add_user(&self, near_address: String, email: String) -> Result<User>;
+    fn get_user(&self, near_address: String) -> Result<User>;
+    fn update_user(&self, near_address: String, profile: UserProfile) -> Result<User>;
+
+    fn add_event(&self, fields: EventFields) -> Result<Event>;
+    fn update_event(&self, id: i64, fields: EventFields) -> Result<Event>;
+    fn get_event(&self, id: i64) -> Result<Event>;
+    fn get_events(&self) -> Result<Vec<Event>>;
+    /// Events from registration through published results.
+    fn get_live_events(&self) -> Result<Vec<Event>>;
+    fn transition_event(&self, id: i64, phase: EventPhase) -> Result<Event>;
+    fn set_event_team_size(&self, id: i64, max_team_size: i64) -> Result<Event>;
+    fn set_event_grace_period(&self, id: i64, minutes: i64) -> Result<Event>;
+    fn set_event_score_normalization(&self, id: i64, enabled: bool) -> Result<Event>;
+
+    fn add_submission(
+        &self,
+        event_id: i64,
+        submit_by: String,
+        fields: SubmissionFields,
+    ) -> Result<Submission>;
+    fn update_submission(&self, id: i64, fields: SubmissionFields) -> Result<Submission>;
+    fn get_submission(&self, id: i64) -> Result<Submission>;
+    fn get_submissions(&self) -> Result<Vec<Submission>>;
+    fn get_submissions_by_event(&self, event_id: i64) -> Result<Vec<Submission>>;
+    /// The submission `address` owns or is a team member of for `event_id`.
+    fn get_user_submission_for_event(&self, address: String, event_id: i64) -> Result<Submission>;
+    /// Whether `address` owns or is a team member of a submission for
+    /// `event_id`. Adding a submission or a team member fails with
+    /// `CONFLICT` when this already holds.
+    fn has_submission_for_event(&self, address: String, event_id: i64) -> Result<bool>;
+    fn transition_submission(&self, id: i64, status: SubmissionStatus) -> Result<Submission>;
+    fn set_submission_track(&self, id: i64, track: String) -> Result<Submission>;
+
+    /// Team members of a submission, not counting its owner.
+    fn get_submission_team(&self, submission_id: i64) -> Result<Vec<TeamMember>>;
+    fn add_team_member(&self, submission_id: i64, near_address: String) -> Result<()>;
+    fn remove_team_member(&self, submission_id: i64, near_address: String) -> Result<()>;
+}
+
+pub struct SqliteStore<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> SqliteStore<'a> {
+    pub fn new(conn: &'a Connection) -> SqliteStore<'a> {
+        SqliteStore { conn }
+    }
+}
+
+impl Store for SqliteStore<'_> {
+    fn add_user(&self, near_address: String, email: String) -> Result<User> {
+        db::add_user(self.conn, near_address, email)
+    }
+
+    fn get_user(&self, near_address: String) -> Result<User> {
+        db::get_user(self.conn, near_address)
+    }
+
+    fn update_user(&self, near_address: String, profile: UserProfile) -> Result<User> {
+        db::update_user(
+            self.conn,
+            near_address,
+            profile.first_name,
+            profile.last_name,
+            profile.is_student,
+            profile.country,
+            profile.git,
+            profile.linkedin,
+            profile.twitter,
+        )
+    }
+
+    fn add_event(&self, fields: EventFields) -> Result<Event> {
+        db::add_event(
+            self.conn,
+            fields.title,
+            fields.event_type,
+            fields.start_date,
+            fields.end_date,
+            fields.logo,
+        )
+    }
+
+    fn update_event(&self, id: i64, fields: EventFields) -> Result<Event> {
+        db::update_event(
+            self.conn,
+            id,
+            fields.title,
+            fields.event_type,
+            fields.start_date,
+            fields.end_date,
+            fields.logo,
+        )
+    }
+
+    fn get_event(&self, id: i64) -> Result<Event> {
+        db::get_event(self.conn, id)
+    }
+
+    fn get_events(&self) -> Result<Vec<Event>> {
+        db::get_events(self.conn)
+    }
+
+    fn get_live_events(&self) -> Result<Vec<Event>> {
+        db::get_live_events(self.conn)
+    }
+
+    fn transition_event(&self, id: i64, phase: EventPhase) -> Result<Event> {
+        db::transition_event(self.conn, id, phase)
+    }
+
+    fn set_event_team_size(&self, id: i64, max_team_size: i64) -> Result<Event> {
+        db::set_event_team_size(self.conn, id, max_team_size)
+    }
+
+    fn set_event_grace_period(&self, id: i64, minutes: i64) -> Result<Event> {
+        db::set_event_grace_period(self.conn, id, minutes)
+    }
+
+    fn set_event_score_normalization(&self, id: i64, enabled: bool) -> Result<Event> {
+        db::set_event_score_normalization(self.conn, id, enabled)
+    }
+
+    fn add_submission(
+        &self,
+        event_id: i64,
+        submit_by: String,
+        fields: SubmissionFields,
+    ) -> Result<Submission> {
+        db::add_submission(
+            self.conn,
+            event_id,
+            fields.name,
+            fields.description,
+            fields.thumbnail,
+            fields.git,
+            fields.live_url,
+            fields.video_url,
+            submit_by,
+        )
+    }
+
+    fn update_submission(&self, id: i64, fields: SubmissionFields) -> Result<Submission> {
+        db::update_submission(
+            self.conn,
+            id,
+            fields.name,
+            fields.description,
+            fields.thumbnail,
+            fields.git,
+            fields.live_url,
+            fields.video_url,
+        )
+    }
+
+    fn get_submission(&self, id: i64) -> Result<Submission> {
+        db::get_submission(self.conn, id)
+    }
+
+    fn get_submissions(&self) -> Result<Vec<Submission>> {
+        db::get_submissions(self.conn)
+    }
+
+    fn get_submissions_by_event(&self, event_id: i64) -> Result<Vec<Submission>> {
+        db::get_submissions_by_event(self.conn, event_id)
+    }
+
+    fn get_user_submission_for_event(&self, address: String, event_id: i64) -> Result<Submission> {
+        db::get_user_submission_for_event(self.conn, address, event_id)
+    }
+
+    fn has_submission_for_event(&self, address: String, event_id: i64) -> Result<bool> {
+        db::has_submission_for_event(self.conn, address, event_id)
+    }
+
+    fn transition_submission(&self, id: i64, status: SubmissionStatus) -> Result<Submission> {
+        db::transition_submission(self.conn, id, status)
+    }
+
+    fn set_submission_track(&self, id: i64, track: String) -> Result<Submission> {
+        db::set_submission_track(self.conn, id, track)
+    }
+
+    fn get_submission_team(&self, submission_id: i64) -> Result<Vec<TeamMember>> {
+        db::get_submission_team(self.conn, submission_id)
+    }
+
+    fn add_team_member(&self, submission_id: i64, near_address: String) -> Result<()> {
+        db::add_team_member(self.conn, submission_id, near_address)
+    }
+
+    fn remove_team_member(&self, submission_id: i64, near_address: String) -> Result<()> {
+        db::remove_team_member(self.conn, submission_id, near_address)
+    }
+}
@@ -0,0 +1,317 @@
+use crate::db::{self, Submission};
+use crate::error::{ErrorCode, Result};
+use crate::schedule;
+use crate::status::{self, SubmissionStatus};
+use crate::store::{Store, SubmissionFields};
+
+// Submission rules shared by the marine exports, written against `Store` so
+// they run on either backend. Callers check who may act on a submission
+// first; these only enforce the event phase, the deadline and the
+// submission's own state.
+
+pub fn create_draft<S: Store>(
+    store: &S,
+    submit_by: String,
+    event_id: i64,
+    fields: SubmissionFields,
+) -> Result<Submission> {
+    store.get_user(submit_by.clone())?;
+
+    let event = store.get_event(event_id)?;
+    status::require_drafts_open(&event)?;
+    schedule::require_within_deadline(&event)?;
+
+    // check if user already submitted, alone or as part of a team. The
+    // unique index and triggers on submissions catch concurrent drafts.
+    if store.has_submission_for_event(submit_by.clone(), event_id)? {
+        return Err(db::get_duplicate_submission_error());
+    }
+
+    store
+        .add_submission(event_id, submit_by, fields)
+        .map_err(|e| match e.code {
+            ErrorCode::Conflict => db::get_duplicate_submission_error(),
+            _ => e,
+        })
+}
+
+pub fn edit_draft<S: Store>(
+    store: &S,
+    submission: &Submission,
+    fields: SubmissionFields,
+) -> Result<Submission> {
+    status::require_draft(submission)?;
+    let event = store.get_event(submission.event_id)?;
+    status::require_drafts_open(&event)?;
+    schedule::require_within_deadline(&event)?;
+
+    store.update_submission(submission.uuid, fields)
+}
+
+pub fn submit<S: Store>(store: &S, submission: &Submission) -> Result<Submission> {
+    let event = store.get_event(submission.event_id)?;
+    status::require_submissions_open(&event)?;
+    schedule::require_within_deadline(&event)?;
+
+    store.transition_submission(submission.uuid, SubmissionStatus::Submitted)
+}
+
+pub fn withdraw<S: Store>(store: &S, submission: &Submission) -> Result<Submission> {
+    let event = store.get_event(submission.event_id)?;
+    status::require_drafts_open(&event)?;
+    schedule::require_within_deadline(&event)?;
+
+    store.transition_submission(submission.uuid, SubmissionStatus::Withdrawn)
+}
+
+/// Organizers only; moves a submitted or withdrawn project back to a draft.
+pub fn reopen<S: Store>(store: &S, submission: &Submission) -> Result<Submission> {
+    let event = store.get_event(submission.event_id)?;
+    status::require_drafts_open(&event)?;
+    schedule::require_within_deadline(&event)?;
+
+    store.transition_submission(submission.uuid, SubmissionStatus::Draft)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, ErrorCode};
+    use crate::memory::MemoryStore;
+    use crate::status::EventPhase;
+    use crate::store::EventFields;
+
+    const ALICE: &str = "alice.near";
+    const BOB: &str = "bob.near";
+    const PAST: &str = "2020-01-01T00:00:00Z";
+
+    fn fields(name: &str) -> SubmissionFields {
+        SubmissionFields {
+            name: name.to_string(),
+            description: "description".to_string(),
+            thumbnail: "".to_string(),
+            git: "https://github.com/academy/project".to_string(),
+            live_url: "".to_string(),
+            video_url: "https://youtu.be/demo".to_string(),
+        }
+    }
+
+    fn error_code<T>(res: Result<T>) -> ErrorCode {
+        res.err().map(|e: Error| e.code).expect("expected an error")
+    }
+
+    /// Store with two users and one event moved through `phases`.
+    fn store_with_event(
+        start_date: &str,
+        end_date: &str,
+        phases: &[EventPhase],
+    ) -> (MemoryStore, i64) {
+        let store = MemoryStore::new();
+        store
+            .add_user(ALICE.to_string(), "alice@example.com".to_string())
+            .unwrap();
+        store
+            .add_user(BOB.to_string(), "bob@example.com".to_string())
+            .unwrap();
+
+        let event = store
+            .add_event(EventFields {
+                title: "Hackathon".to_string(),
+                event_type: "hackathon".to_string(),
+                start_date: start_date.to_string(),
+                end_date: end_date.to_string(),
+                logo: "".to_string(),
+            })
+            .unwrap();
+        for phase in phases {
+            store.transition_event(event.id, *phase).unwrap();
+        }
+
+        (store, event.id)
+    }
+
+    fn open_event() -> (MemoryStore, i64) {
+        store_with_event(PAST, "", &[EventPhase::SubmissionsOpen])
+    }
+
+    #[test]
+    fn first_draft_is_created_as_draft() {
+        let (store, event_id) = open_event();
+
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+
+        assert_eq!(submission.status, SubmissionStatus::Draft.code());
+        assert_eq!(submission.submit_by, ALICE);
+        assert_eq!(submission.project_name, "Rocket");
+    }
+
+    #[test]
+    fn draft_needs_a_registered_user() {
+        let (store, event_id) = open_event();
+
+        let res = create_draft(&store, "carol.near".to_string(), event_id, fields("Rocket"));
+
+        assert_eq!(error_code(res), ErrorCode::NotFound);
+    }
+
+    #[test]
+    fn second_draft_for_the_event_is_rejected() {
+        let (store, event_id) = open_event();
+        create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+
+        let res = create_draft(&store, ALICE.to_string(), event_id, fields("Other"));
+
+        assert_eq!(error_code(res), ErrorCode::Conflict);
+    }
+
+    #[test]
+    fn team_member_cannot_draft_their_own_project() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        store
+            .add_team_member(submission.uuid, BOB.to_string())
+            .unwrap();
+
+        let res = create_draft(&store, BOB.to_string(), event_id, fields("Other"));
+
+        assert_eq!(error_code(res), ErrorCode::Conflict);
+    }
+
+    #[test]
+    fn owner_of_a_draft_cannot_join_another_team() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        create_draft(&store, BOB.to_string(), event_id, fields("Other")).unwrap();
+
+        let res = store.add_team_member(submission.uuid, BOB.to_string());
+
+        assert_eq!(error_code(res), ErrorCode::Conflict);
+    }
+
+    #[test]
+    fn drafts_need_an_open_event() {
+        let (store, event_id) = store_with_event(PAST, "", &[]);
+
+        let res = create_draft(&store, ALICE.to_string(), event_id, fields("Rocket"));
+
+        assert_eq!(error_code(res), ErrorCode::InvalidState);
+    }
+
+    #[test]
+    fn drafts_are_rejected_before_the_start() {
+        let (store, event_id) =
+            store_with_event("2999-01-01T00:00:00Z", "", &[EventPhase::SubmissionsOpen]);
+
+        let res = create_draft(&store, ALICE.to_string(), event_id, fields("Rocket"));
+
+        assert_eq!(error_code(res), ErrorCode::NotStarted);
+    }
+
+    #[test]
+    fn drafts_are_rejected_after_the_deadline() {
+        let (store, event_id) =
+            store_with_event(PAST, "2021-01-01T00:00:00Z", &[EventPhase::SubmissionsOpen]);
+
+        let res = create_draft(&store, ALICE.to_string(), event_id, fields("Rocket"));
+
+        assert_eq!(error_code(res), ErrorCode::DeadlinePassed);
+    }
+
+    #[test]
+    fn edit_draft_updates_the_project() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+
+        let edited = edit_draft(&store, &submission, fields("Rocket 2")).unwrap();
+
+        assert_eq!(edited.project_name, "Rocket 2");
+        assert_eq!(
+            store.get_submission(submission.uuid).unwrap().project_name,
+            "Rocket 2"
+        );
+    }
+
+    #[test]
+    fn submitted_projects_cannot_be_edited() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        let submitted = submit(&store, &submission).unwrap();
+
+        let res = edit_draft(&store, &submitted, fields("Rocket 2"));
+
+        assert_eq!(error_code(res), ErrorCode::Locked);
+    }
+
+    #[test]
+    fn submitting_needs_submissions_open() {
+        let (store, event_id) = store_with_event(PAST, "", &[EventPhase::RegistrationOpen]);
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+
+        let res = submit(&store, &submission);
+
+        assert_eq!(error_code(res), ErrorCode::InvalidState);
+    }
+
+    #[test]
+    fn submitted_project_cannot_be_submitted_again() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        let submitted = submit(&store, &submission).unwrap();
+
+        assert_eq!(submitted.status, SubmissionStatus::Submitted.code());
+        assert_eq!(
+            error_code(submit(&store, &submitted)),
+            ErrorCode::InvalidState
+        );
+    }
+
+    #[test]
+    fn withdrawn_project_can_be_reopened() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        let submitted = submit(&store, &submission).unwrap();
+
+        let withdrawn = withdraw(&store, &submitted).unwrap();
+        assert_eq!(withdrawn.status, SubmissionStatus::Withdrawn.code());
+
+        let reopened = reopen(&store, &withdrawn).unwrap();
+        assert_eq!(reopened.status, SubmissionStatus::Draft.code());
+        assert_eq!(reopened.status_name, "draft");
+    }
+
+    #[test]
+    fn draft_cannot_be_reopened() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+
+        assert_eq!(
+            error_code(reopen(&store, &submission)),
+            ErrorCode::InvalidState
+        );
+    }
+
+    #[test]
+    fn projects_cannot_be_withdrawn_during_judging() {
+        let (store, event_id) = open_event();
+        let submission =
+            create_draft(&store, ALICE.to_string(), event_id, fields("Rocket")).unwrap();
+        let submitted = submit(&store, &submission).unwrap();
+        store
+            .transition_event(event_id, EventPhase::Judging)
+            .unwrap();
+
+        assert_eq!(
+            error_code(withdraw(&store, &submitted)),
+            ErrorCode::InvalidState
+        );
+    }
+}
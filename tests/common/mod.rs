@@ -0,0 +1,103 @@
+// Shared by the marine_test suites. They drive the compiled module, so run
+// build.sh (or `marine build --release` and copy academy_backend.wasm into
+// artifacts/) before `cargo test`. The database outlives a run, so every
+// suite works with fresh keys, peers and events instead of fixed ones.
+
+use ed25519_dalek::{Keypair, PublicKey, SecretKey, Signer};
+use marine_rs_sdk_test::CallParameters;
+
+pub const OWNER: &str = "test-owner";
+pub const START_DATE: &str = "2020-01-01T00:00:00Z";
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).unwrap();
+    hex::encode(bytes)
+}
+
+/// Call parameters of `peer_id` on the service owned by `OWNER`.
+pub fn call_as(peer_id: &str) -> CallParameters {
+    CallParameters {
+        init_peer_id: peer_id.to_string(),
+        service_creator_peer_id: OWNER.to_string(),
+        ..Default::default()
+    }
+}
+
+pub fn new_peer() -> CallParameters {
+    call_as(&format!("peer-{}", random_hex(8)))
+}
+
+/// A NEAR implicit account, which proves itself by its key alone.
+pub struct NearKey {
+    keypair: Keypair,
+}
+
+impl NearKey {
+    pub fn generate() -> NearKey {
+        let mut bytes = [0u8; 32];
+        getrandom::getrandom(&mut bytes).unwrap();
+        let secret = SecretKey::from_bytes(&bytes).unwrap();
+        let public = PublicKey::from(&secret);
+
+        NearKey {
+            keypair: Keypair { secret, public },
+        }
+    }
+
+    pub fn address(&self) -> String {
+        hex::encode(self.keypair.public.as_bytes())
+    }
+
+    pub fn public_key(&self) -> String {
+        format!(
+            "ed25519:{}",
+            bs58::encode(self.keypair.public.as_bytes()).into_string()
+        )
+    }
+
+    pub fn sign(&self, message: &str) -> String {
+        bs58::encode(self.keypair.sign(message.as_bytes()).to_bytes()).into_string()
+    }
+}
+
+/// Binds `$key` to the peer in `$cp` and registers it with `$email`,
+/// returning the new user.
+macro_rules! register {
+    ($backend:expr, $key:expr, $cp:expr, $email:expr) => {{
+        let challenge = $backend.request_near_challenge_cp($key.address(), $cp.clone());
+        assert!(challenge.success, "{}", challenge.err_msg);
+
+        let verified = $backend.verify_near_address_cp(
+            $key.address(),
+            $key.public_key(),
+            $key.sign(&challenge.message),
+            $cp.clone(),
+        );
+        assert!(verified.success, "{}", verified.err_msg);
+
+        let user = $backend.register_user_cp($key.address(), $email, $cp.clone());
+        assert!(user.success, "{}", user.err_msg);
+        user
+    }};
+}
+
+/// Creates an open ended event titled `$title` that takes submissions.
+macro_rules! open_event {
+    ($backend:expr, $title:expr) => {{
+        let owner = $crate::common::call_as($crate::common::OWNER);
+        let event = $backend.add_event_cp(
+            $title,
+            "hackathon".to_string(),
+            $crate::common::START_DATE.to_string(),
+            "".to_string(),
+            "".to_string(),
+            owner.clone(),
+        );
+        assert!(event.success, "{}", event.err_msg);
+
+        let event = $backend.transition_event_cp(event.id, "submissions_open".to_string(), owner);
+        assert!(event.success, "{}", event.err_msg);
+        event
+    }};
+}
@@ -0,0 +1,431 @@
+#[macro_use]
+mod common;
+
+use common::{call_as, new_peer, NearKey, OWNER, START_DATE};
+use marine_rs_sdk_test::marine_test;
+
+// Quotes, statement separators, comment markers and non-ASCII text have to
+// come back exactly as they went in. Every query binds its values, so none
+// of these can end a string literal or start another statement.
+const TRICKY: &[&str] = &[
+    "O'Brien; drop table users;--",
+    "🚀 launch",
+    "\"double\" and 'single' quotes",
+    "back\\slash, 100% and _under_",
+    "'); delete from submissions; --",
+];
+
+#[marine_test(config_path = "../tests/Config.toml", modules_dir = "../artifacts")]
+fn text_round_trips_through_exports(
+    mut backend: marine_test_env::academy_backend::ModuleInterface,
+) {
+    use marine_test_env::academy_backend::{
+        CriterionScore, EventFilter, PageRequest, SubmissionFilter, UserFilter,
+    };
+
+    let owner = call_as(OWNER);
+    let init = backend.init_service_cp(owner.clone());
+    assert!(init.success, "{}", init.err_msg);
+
+    // users
+    let key = NearKey::generate();
+    let peer = new_peer();
+    let email = format!("o'brien+🚀;--{}@example.com", key.address());
+    let user = register!(backend, key, peer, email.clone());
+    assert_eq!(user.email, email);
+
+    for text in TRICKY {
+        let text = text.to_string();
+        let user = backend.update_user_cp(
+            key.address(),
+            text.clone(),
+            text.clone(),
+            1,
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            peer.clone(),
+        );
+        assert!(user.success, "{}", user.err_msg);
+
+        let stored = backend.get_user_cp(key.address(), peer.clone());
+        assert_eq!(stored.email, email);
+        assert_eq!(stored.first_name, text);
+        assert_eq!(stored.last_name, text);
+        assert_eq!(stored.country, text);
+        assert_eq!(stored.git_handler, text);
+        assert_eq!(stored.linkedin_handler, text);
+        assert_eq!(stored.twitter_handler, text);
+
+        let found = backend.search_users_cp(text.clone(), owner.clone());
+        assert!(found.success, "{}", found.err_msg);
+    }
+
+    // profiles carry emails, so only organizers may search them
+    let hidden = backend.search_users_cp(TRICKY[0].to_string(), peer.clone());
+    assert_eq!(hidden.err_code, "UNAUTHORIZED");
+
+    let missing = backend.get_user_cp("' or '1'='1".to_string(), peer.clone());
+    assert!(!missing.success);
+    assert_eq!(missing.err_code, "NOT_FOUND");
+
+    // events
+    let event = open_event!(backend, TRICKY[0].to_string());
+    assert_eq!(event.title, TRICKY[0]);
+
+    for text in TRICKY {
+        let text = text.to_string();
+        let updated = backend.update_event_cp(
+            event.id,
+            text.clone(),
+            text.clone(),
+            START_DATE.to_string(),
+            "".to_string(),
+            text.clone(),
+            owner.clone(),
+        );
+        assert!(updated.success, "{}", updated.err_msg);
+
+        let stored = backend.get_event_cp(event.id, peer.clone());
+        assert_eq!(stored.title, text);
+        assert_eq!(stored.event_type, text);
+        assert_eq!(stored.logo, text);
+    }
+
+    for text in TRICKY {
+        let criterion = backend.add_rubric_criterion_cp(
+            event.id,
+            text.to_string(),
+            text.to_string(),
+            1.0,
+            10,
+            owner.clone(),
+        );
+        assert!(criterion.success, "{}", criterion.err_msg);
+    }
+    let rubric = backend.get_rubric_cp(event.id, owner.clone());
+    let names: Vec<&str> = rubric.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, TRICKY.to_vec());
+
+    // submissions
+    let text = TRICKY[0].to_string();
+    let submission = backend.draft_cp(
+        event.id,
+        text.clone(),
+        text.clone(),
+        text.clone(),
+        text.clone(),
+        text.clone(),
+        text,
+        peer.clone(),
+    );
+    assert!(submission.success, "{}", submission.err_msg);
+
+    for text in TRICKY {
+        let text = text.to_string();
+        let updated = backend.update_submission_cp(
+            submission.uuid,
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            text.clone(),
+            peer.clone(),
+        );
+        assert!(updated.success, "{}", updated.err_msg);
+
+        let tracked = backend.set_submission_track_cp(submission.uuid, text.clone(), peer.clone());
+        assert!(tracked.success, "{}", tracked.err_msg);
+
+        let stored = backend.get_submission_cp(submission.uuid, peer.clone());
+        assert_eq!(stored.project_name, text);
+        assert_eq!(stored.description, text);
+        assert_eq!(stored.thumbnail, text);
+        assert_eq!(stored.git_url, text);
+        assert_eq!(stored.live_demo_url, text);
+        assert_eq!(stored.video_demo_url, text);
+        // tracks are stored trimmed and lowercased
+        assert_eq!(stored.track, text.trim().to_lowercase());
+
+        let found = backend.search_submissions_cp(text.clone(), event.id, 1, peer.clone());
+        assert!(found.success, "{}", found.err_msg);
+    }
+
+    // lookups by address
+    let own = backend.get_user_event_submission_cp(key.address(), event.id, peer.clone());
+    assert_eq!(own.uuid, submission.uuid);
+    for text in TRICKY {
+        let missing =
+            backend.get_user_event_submission_cp(text.to_string(), event.id, peer.clone());
+        assert_eq!(missing.err_code, "NOT_FOUND");
+    }
+
+    // named accounts, organizations and roles
+    for text in TRICKY {
+        let text = text.to_string();
+        let attested =
+            backend.add_near_access_key_cp(text.clone(), key.public_key(), owner.clone());
+        assert!(attested.success, "{}", attested.err_msg);
+        assert!(backend
+            .get_near_access_keys_cp(text.clone(), peer.clone())
+            .contains(&key.public_key()));
+
+        let named = new_peer();
+        let challenge = backend.request_near_challenge_cp(text.clone(), named.clone());
+        assert!(challenge.success, "{}", challenge.err_msg);
+        assert_eq!(challenge.near_address, text);
+        assert!(challenge.message.contains(&text));
+        let verified = backend.verify_near_address_cp(
+            text.clone(),
+            key.public_key(),
+            key.sign(&challenge.message),
+            named,
+        );
+        assert!(verified.success, "{}", verified.err_msg);
+
+        let granted = backend.grant_role_cp(text.clone(), "judge".to_string(), owner.clone());
+        assert!(granted.success, "{}", granted.err_msg);
+        assert_eq!(
+            backend.get_roles_cp(text.clone(), owner.clone()),
+            vec!["judge"]
+        );
+        let revoked = backend.revoke_role_cp(text.clone(), "judge".to_string(), owner.clone());
+        assert!(revoked.success, "{}", revoked.err_msg);
+        assert!(backend.get_roles_cp(text.clone(), owner.clone()).is_empty());
+
+        let unknown = backend.grant_role_cp(peer.init_peer_id.clone(), text, owner.clone());
+        assert_eq!(unknown.err_code, "VALIDATION");
+    }
+
+    let organizations: Vec<String> = TRICKY.iter().map(|t| t.to_string()).collect();
+    let declared = backend.set_my_organizations_cp(organizations, peer.clone());
+    assert!(declared.success, "{}", declared.err_msg);
+    // organizations are stored lowercased with whitespace collapsed
+    let mut expected_organizations: Vec<String> = TRICKY
+        .iter()
+        .map(|t| {
+            t.split_whitespace()
+                .collect::<Vec<&str>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .collect();
+    expected_organizations.sort();
+    assert_eq!(
+        backend.get_user_organizations_cp(key.address(), peer.clone()),
+        expected_organizations
+    );
+
+    // invitations
+    let mate = NearKey::generate();
+    let mate_cp = new_peer();
+    let mate_email = format!("'); --🚀{}@example.com", mate.address());
+    register!(backend, mate, mate_cp, mate_email.clone());
+    for text in TRICKY {
+        let invitation =
+            backend.invite_team_member_cp(submission.uuid, text.to_string(), peer.clone());
+        assert_eq!(invitation.err_code, "NOT_FOUND");
+    }
+    let invitation = backend.invite_team_member_cp(submission.uuid, mate_email, peer.clone());
+    assert!(invitation.success, "{}", invitation.err_msg);
+    assert_eq!(invitation.invitee, mate.address());
+
+    // listings
+    let page = |cursor: &str, sort_by: &str| PageRequest {
+        limit: 0,
+        cursor: cursor.to_string(),
+        sort_by: sort_by.to_string(),
+        descending: false,
+    };
+    for text in TRICKY {
+        let text = text.to_string();
+        let users = backend.list_users_cp(
+            UserFilter {
+                country: text.clone(),
+                is_student: 0,
+            },
+            page("", ""),
+            owner.clone(),
+        );
+        assert!(users.success, "{}", users.err_msg);
+        assert!(users.items.iter().all(|u| u.country == text));
+
+        let events = backend.list_events_cp(
+            EventFilter {
+                status: 0,
+                event_type: text.clone(),
+                starts_from: "".to_string(),
+                starts_to: "".to_string(),
+            },
+            page("", ""),
+            peer.clone(),
+        );
+        assert!(events.success, "{}", events.err_msg);
+        assert!(events.items.iter().all(|e| e.event_type == text));
+
+        let submissions = backend.list_submissions_cp(
+            SubmissionFilter {
+                event_id: event.id,
+                status: 0,
+                submit_by: text.clone(),
+                created_from: "".to_string(),
+                created_to: "".to_string(),
+            },
+            page("", ""),
+            peer.clone(),
+        );
+        assert!(submissions.success, "{}", submissions.err_msg);
+        assert_eq!(submissions.total, 0);
+
+        // dates, cursors and sort columns never reach the SQL as text
+        let dated = backend.list_events_cp(
+            EventFilter {
+                status: 0,
+                event_type: "".to_string(),
+                starts_from: text.clone(),
+                starts_to: text.clone(),
+            },
+            page("", ""),
+            peer.clone(),
+        );
+        assert_eq!(dated.err_code, "VALIDATION");
+        let dated = backend.list_submissions_cp(
+            SubmissionFilter {
+                event_id: 0,
+                status: 0,
+                submit_by: "".to_string(),
+                created_from: text.clone(),
+                created_to: text.clone(),
+            },
+            page("", ""),
+            peer.clone(),
+        );
+        assert_eq!(dated.err_code, "VALIDATION");
+        let resumed = backend.list_users_cp(
+            UserFilter {
+                country: "".to_string(),
+                is_student: 0,
+            },
+            page(&text, ""),
+            owner.clone(),
+        );
+        assert_eq!(resumed.err_code, "VALIDATION");
+        let sorted = backend.list_users_cp(
+            UserFilter {
+                country: "".to_string(),
+                is_student: 0,
+            },
+            page("", &text),
+            owner.clone(),
+        );
+        assert_eq!(sorted.err_code, "VALIDATION");
+
+        let moved = backend.transition_event_cp(event.id, text, owner.clone());
+        assert_eq!(moved.err_code, "VALIDATION");
+    }
+
+    let own = backend.list_submissions_cp(
+        SubmissionFilter {
+            event_id: event.id,
+            status: 0,
+            submit_by: key.address(),
+            created_from: "".to_string(),
+            created_to: "".to_string(),
+        },
+        page("", ""),
+        peer.clone(),
+    );
+    assert!(own.success, "{}", own.err_msg);
+    assert_eq!(own.total, 1);
+    assert_eq!(own.items[0].project_name, TRICKY[TRICKY.len() - 1]);
+
+    // judging
+    let judge = NearKey::generate();
+    let judge_cp = new_peer();
+    register!(
+        backend,
+        judge,
+        judge_cp,
+        format!("{}@example.com", judge.address())
+    );
+    let granted = backend.grant_role_cp(
+        judge_cp.init_peer_id.clone(),
+        "judge".to_string(),
+        owner.clone(),
+    );
+    assert!(granted.success, "{}", granted.err_msg);
+    let added = backend.add_event_judge_cp(event.id, judge.address(), owner.clone());
+    assert!(added.success, "{}", added.err_msg);
+
+    let tracks: Vec<String> = TRICKY.iter().map(|t| t.to_string()).collect();
+    let tracked = backend.set_judge_tracks_cp(event.id, judge.address(), tracks, owner.clone());
+    assert!(tracked.success, "{}", tracked.err_msg);
+    // judge tracks are stored trimmed and lowercased, like submission tracks
+    let mut expected: Vec<String> = TRICKY.iter().map(|t| t.trim().to_lowercase()).collect();
+    expected.sort();
+    let judge_tracks = backend.get_judge_tracks_cp(event.id, judge.address(), peer.clone());
+    assert_eq!(judge_tracks, expected);
+    for text in TRICKY {
+        let tracked =
+            backend.set_judge_tracks_cp(event.id, text.to_string(), vec![], owner.clone());
+        assert_eq!(tracked.err_code, "NOT_FOUND");
+    }
+
+    let submitted = backend.submit_cp(submission.uuid, peer.clone());
+    assert!(submitted.success, "{}", submitted.err_msg);
+    let judging = backend.transition_event_cp(event.id, "judging".to_string(), owner.clone());
+    assert!(judging.success, "{}", judging.err_msg);
+
+    let scores: Vec<CriterionScore> = rubric
+        .iter()
+        .map(|c| CriterionScore {
+            criterion_id: c.id,
+            score: 5,
+        })
+        .collect();
+    for text in TRICKY {
+        let review = backend.score_submission_cp(
+            submission.uuid,
+            scores.clone(),
+            text.to_string(),
+            judge_cp.clone(),
+        );
+        assert!(review.success, "{}", review.err_msg);
+        assert_eq!(review.comment, *text);
+
+        let reviews = backend.get_scores_cp(submission.uuid, owner.clone());
+        assert_eq!(reviews.len(), 1);
+        assert_eq!(reviews[0].comment, *text);
+    }
+
+    // backups
+    for text in TRICKY {
+        let restored = backend.restore_service_cp(text.to_string(), owner.clone());
+        assert_eq!(restored.err_code, "VALIDATION");
+    }
+    let backup = backend.backup_service_cp(owner.clone());
+    assert!(backup.success, "{}", backup.err_msg);
+    let restored = backend.restore_service_cp(backup.dump, owner.clone());
+    assert!(restored.success, "{}", restored.err_msg);
+
+    // nothing above got to run as SQL, and the restore brought it all back
+    let stored = backend.get_user_cp(key.address(), peer.clone());
+    assert!(stored.success, "{}", stored.err_msg);
+    assert_eq!(stored.email, email);
+    assert_eq!(stored.first_name, TRICKY[TRICKY.len() - 1]);
+    let stored = backend.get_submission_cp(submission.uuid, peer.clone());
+    assert!(stored.success, "{}", stored.err_msg);
+    assert_eq!(stored.project_name, TRICKY[TRICKY.len() - 1]);
+    assert_eq!(
+        backend.get_judge_tracks_cp(event.id, judge.address(), peer.clone()),
+        judge_tracks
+    );
+    let reviews = backend.get_scores_cp(submission.uuid, owner);
+    assert_eq!(reviews[0].comment, TRICKY[TRICKY.len() - 1]);
+    assert_eq!(
+        backend.get_user_organizations_cp(key.address(), peer),
+        expected_organizations
+    );
+}
@@ -0,0 +1,161 @@
+-- A database as it stood at schema version 8, with one user owning two
+-- submissions for the same event. Migration 9 refuses to run on it, so the
+-- service stays at version 8. Shipped migrations never change, so this
+-- stays valid; the statements are those of migrations 1 to 8.
+
+create table schema_version (
+  version INTEGER not null primary key,
+  name TEXT not null,
+  applied_at DATETIME default CURRENT_TIMESTAMP
+);
+
+-- 1 create_initial_tables
+create table if not exists users (
+  near_address TEXT unique not null primary key,
+  email TEXT unique not null,
+  first_name TEXT default null,
+  last_name TEXT default null,
+  is_student INTEGER,
+  country TEXT default null,
+  git_handler TEXT default null,
+  linkedin_handler TEXT default null,
+  twitter_handler TEXT default null
+) without rowid;
+
+create table if not exists events (
+  id INTEGER not null primary key AUTOINCREMENT,
+  type TEXT not null,
+  title TEXT not null,
+  start_date TEXT not null,
+  end_date TEXT default null,
+  logo TEXT not null,
+  status INTEGER not null
+);
+
+create table if not exists submissions (
+  uuid INTEGER not null primary key AUTOINCREMENT,
+  event_id INTEGER not null,
+  project_name TEXT not null,
+  description TEXT not null,
+  thumbnail TEXT default null,
+  git_url TEXT not null,
+  live_demo_url TEXT default null,
+  video_demo_url TEXT not null,
+  submit_by TEXT not null,
+  status INTEGER not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  FOREIGN KEY (submit_by) REFERENCES users,
+  FOREIGN KEY (event_id) REFERENCES events
+);
+
+create table if not exists submission_team (
+  uuid INTEGER not null primary key AUTOINCREMENT,
+  near_address TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP
+);
+
+insert into schema_version (version, name) values (1, 'create_initial_tables');
+
+-- 2 create_roles
+create table if not exists roles (
+  peer_id TEXT not null,
+  role TEXT not null,
+  granted_by TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  primary key (peer_id, role)
+);
+
+insert into schema_version (version, name) values (2, 'create_roles');
+
+-- 3 create_near_ownership
+create table if not exists near_challenges (
+  near_address TEXT not null,
+  peer_id TEXT not null,
+  nonce TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  primary key (near_address, peer_id)
+);
+
+create table if not exists peer_bindings (
+  peer_id TEXT not null primary key,
+  near_address TEXT unique not null,
+  public_key TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP
+);
+
+insert into schema_version (version, name) values (3, 'create_near_ownership');
+
+-- 4 create_submission_teams
+drop table if exists submission_team;
+
+create table submission_team (
+  submission_id INTEGER not null,
+  near_address TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  primary key (submission_id, near_address),
+  FOREIGN KEY (submission_id) REFERENCES submissions,
+  FOREIGN KEY (near_address) REFERENCES users
+);
+
+alter table events add column max_team_size INTEGER not null default 5;
+
+insert into schema_version (version, name) values (4, 'create_submission_teams');
+
+-- 5 create_team_invitations
+create table if not exists team_invitations (
+  id INTEGER not null primary key AUTOINCREMENT,
+  submission_id INTEGER not null,
+  invitee TEXT not null,
+  invited_by TEXT not null,
+  status INTEGER not null,
+  expires_at DATETIME not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  FOREIGN KEY (submission_id) REFERENCES submissions,
+  FOREIGN KEY (invitee) REFERENCES users
+);
+
+insert into schema_version (version, name) values (5, 'create_team_invitations');
+
+-- 6 event_lifecycle_phases
+update events set status = case status when 1 then 3 when 2 then 4 else status end;
+
+insert into schema_version (version, name) values (6, 'event_lifecycle_phases');
+
+-- 7 event_grace_period
+alter table events add column grace_period_minutes INTEGER not null default 0;
+
+insert into schema_version (version, name) values (7, 'event_grace_period');
+
+-- 8 create_submission_revisions
+create table if not exists submission_revisions (
+  submission_id INTEGER not null,
+  revision INTEGER not null,
+  project_name TEXT not null,
+  description TEXT not null,
+  thumbnail TEXT default null,
+  git_url TEXT not null,
+  live_demo_url TEXT default null,
+  video_demo_url TEXT not null,
+  changed_fields TEXT not null default '',
+  edited_by TEXT not null,
+  created_at DATETIME default CURRENT_TIMESTAMP,
+  primary key (submission_id, revision),
+  FOREIGN KEY (submission_id) REFERENCES submissions
+);
+
+insert into submission_revisions (submission_id, revision, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, edited_by, created_at)
+select uuid, 1, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, '', created_at
+from submissions;
+
+insert into schema_version (version, name) values (8, 'create_submission_revisions');
+
+-- data
+insert into users (near_address, email) values ('dup.near', 'dup@example.com');
+
+insert into events (id, type, title, start_date, end_date, logo, status)
+values (1, 'hackathon', 'Held back', '2020-01-01T00:00:00Z', '', '', 3);
+
+insert into submissions (uuid, event_id, project_name, description, thumbnail, git_url, live_demo_url, video_demo_url, submit_by, status)
+values
+  (1, 1, 'First', 'first try', '', 'https://github.com/academy/first', '', '', 'dup.near', 1),
+  (2, 1, 'Second', 'second try', '', 'https://github.com/academy/second', '', '', 'dup.near', 1);
@@ -0,0 +1,152 @@
+#[macro_use]
+mod common;
+
+use common::{call_as, new_peer, NearKey, OWNER};
+use marine_rs_sdk_test::marine_test;
+use serde_json::{json, Value};
+
+/// Appends `row` to `table` in a backup dump. With `again` the row goes into
+/// a second entry for the table at the end of the dump, so it is inserted
+/// after every other table has been restored.
+fn with_row(dump: &str, table: &str, row: Value, again: bool) -> String {
+    let mut dump: Value = serde_json::from_str(dump).unwrap();
+    let tables = dump["tables"].as_array_mut().unwrap();
+    let entry = tables.iter_mut().find(|t| t["name"] == table).unwrap();
+
+    if again {
+        let columns = entry["columns"].clone();
+        tables.push(json!({ "name": table, "columns": columns, "rows": [row] }));
+    } else {
+        entry["rows"].as_array_mut().unwrap().push(row);
+    }
+    dump.to_string()
+}
+
+/// Copy of the dumped submission `uuid` with `submit_by` replaced and the id
+/// left for SQLite to assign.
+fn copy_submission(dump: &str, uuid: i64, submit_by: &str) -> Value {
+    let dump: Value = serde_json::from_str(dump).unwrap();
+    let table = dump["tables"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|t| t["name"] == "submissions")
+        .unwrap();
+    let column = |name: &str| {
+        table["columns"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .position(|c| c == name)
+            .unwrap()
+    };
+
+    let mut row = table["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|r| r[column("uuid")] == uuid)
+        .unwrap()
+        .clone();
+    row[column("uuid")] = Value::Null;
+    row[column("submit_by")] = json!(submit_by);
+    row
+}
+
+#[marine_test(config_path = "../tests/Config.toml", modules_dir = "../artifacts")]
+fn one_submission_per_user_and_event(
+    mut backend: marine_test_env::academy_backend::ModuleInterface,
+) {
+    let owner = call_as(OWNER);
+    let init = backend.init_service_cp(owner.clone());
+    assert!(init.success, "{}", init.err_msg);
+
+    let (alice, alice_cp) = (NearKey::generate(), new_peer());
+    let (bob, bob_cp) = (NearKey::generate(), new_peer());
+    let (carol, carol_cp) = (NearKey::generate(), new_peer());
+    for (key, cp) in [(&alice, &alice_cp), (&bob, &bob_cp), (&carol, &carol_cp)] {
+        register!(backend, key, cp, format!("{}@example.com", key.address()));
+    }
+
+    let event = open_event!(backend, "One project each".to_string());
+    macro_rules! draft {
+        ($cp:expr, $name:expr) => {
+            backend.draft_cp(
+                event.id,
+                $name.to_string(),
+                "".to_string(),
+                "".to_string(),
+                "https://github.com/academy/project".to_string(),
+                "".to_string(),
+                "".to_string(),
+                $cp.clone(),
+            )
+        };
+    }
+
+    // first draft
+    let rocket = draft!(alice_cp, "Rocket");
+    assert!(rocket.success, "{}", rocket.err_msg);
+    assert_eq!(rocket.submit_by, alice.address());
+
+    // duplicate draft
+    let again = draft!(alice_cp, "Rocket again");
+    assert!(!again.success);
+    assert_eq!(again.err_code, "CONFLICT");
+
+    // a team member's duplicate
+    let joined = backend.add_team_member_cp(rocket.uuid, bob.address(), owner.clone());
+    assert!(joined.success, "{}", joined.err_msg);
+    let own = draft!(bob_cp, "Bob's own");
+    assert!(!own.success);
+    assert_eq!(own.err_code, "CONFLICT");
+
+    // the owner of another project cannot join
+    let other = draft!(carol_cp, "Carol's own");
+    assert!(other.success, "{}", other.err_msg);
+    let joined = backend.add_team_member_cp(rocket.uuid, carol.address(), owner.clone());
+    assert!(!joined.success);
+    assert_eq!(joined.err_code, "CONFLICT");
+
+    // Restoring writes rows without going through the exports, so the schema
+    // itself has to refuse duplicates there.
+    let backup = backend.backup_service_cp(owner.clone());
+    assert!(backup.success, "{}", backup.err_msg);
+    let dump = backup.dump;
+
+    let duplicates = [
+        // unique index on (event_id, submit_by)
+        with_row(
+            &dump,
+            "submissions",
+            copy_submission(&dump, rocket.uuid, &alice.address()),
+            false,
+        ),
+        // insert trigger on submissions, bob is already on alice's team
+        with_row(
+            &dump,
+            "submissions",
+            copy_submission(&dump, rocket.uuid, &bob.address()),
+            true,
+        ),
+        // insert trigger on submission_team, carol owns a project
+        with_row(
+            &dump,
+            "submission_team",
+            json!([rocket.uuid, carol.address(), null]),
+            false,
+        ),
+    ];
+    for dump in duplicates {
+        let restored = backend.restore_service_cp(dump, owner.clone());
+        assert!(!restored.success);
+        assert_eq!(restored.err_code, "CONFLICT", "{}", restored.err_msg);
+    }
+
+    let submissions = backend.get_event_submissions_cp(event.id, owner);
+    let mut owners: Vec<String> = submissions.into_iter().map(|s| s.submit_by).collect();
+    owners.sort();
+    let mut expected = vec![alice.address(), carol.address()];
+    expected.sort();
+    assert_eq!(owners, expected);
+}
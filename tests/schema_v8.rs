@@ -0,0 +1,56 @@
+use marine_rs_sdk_test::{marine_test, CallParameters};
+
+// Matches envs in Config.v8.toml.
+const DB_PATH: &str = "/tmp/academy_backend_v8_test.sqlite";
+const OWNER: &str = "test-owner";
+
+fn call_as(peer_id: &str) -> CallParameters {
+    CallParameters {
+        init_peer_id: peer_id.to_string(),
+        service_creator_peer_id: OWNER.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Writes a fresh database at version 8 whose submissions keep migration 9
+/// from applying.
+fn create_held_back_database() {
+    let _ = std::fs::remove_file(DB_PATH);
+    let conn = rusqlite::Connection::open(DB_PATH).unwrap();
+    conn.execute_batch(include_str!("fixtures/schema_v8.sql"))
+        .unwrap();
+}
+
+#[marine_test(config_path = "../tests/Config.v8.toml", modules_dir = "../artifacts")]
+fn reads_work_while_migration_9_is_blocked(
+    mut backend: marine_test_env::academy_backend::ModuleInterface,
+) {
+    create_held_back_database();
+    let owner = call_as(OWNER);
+    let peer = call_as("some-peer");
+
+    let migrated = backend.migrate_service_cp(owner);
+    assert!(!migrated.success);
+    assert_eq!(migrated.err_code, "CONFLICT");
+    assert_eq!(migrated.version, 8);
+    assert_eq!(migrated.duplicate_submissions.len(), 1);
+    let duplicate = &migrated.duplicate_submissions[0];
+    assert_eq!(duplicate.event_id, 1);
+    assert_eq!(duplicate.submit_by, "dup.near");
+    assert_eq!(duplicate.submission_ids, vec![1, 2]);
+
+    // events have no normalize_scores column yet
+    let event = backend.get_event_cp(1, peer.clone());
+    assert!(event.success, "{}", event.err_msg);
+    assert_eq!(event.title, "Held back");
+    assert_eq!(event.event_type, "hackathon");
+    assert!(event.normalize_scores);
+    assert_eq!(backend.get_events_cp(peer.clone()).len(), 1);
+
+    // submissions have no track column yet
+    let submission = backend.get_submission_cp(2, peer.clone());
+    assert!(submission.success, "{}", submission.err_msg);
+    assert_eq!(submission.project_name, "Second");
+    assert_eq!(submission.track, "");
+    assert_eq!(backend.get_event_submissions_cp(1, peer).len(), 2);
+}